@@ -3,21 +3,38 @@
 //! discovery, app-interface attach, lair or client-side zome-call signing,
 //! and typed msgpack zome calls.
 
+use crate::credentials::{copy_credentials, CredentialStore};
+use crate::errors::{classify, CallTarget, HamError, ZomeCall};
+use crate::retry::{RetryPolicy, RetryState};
+use crate::signals::{RawSignal, SignalStream};
+use crate::signing::{CellSigner, LairCellSigner};
 use anyhow::{Context, Result};
 use holochain_client::{
-    AdminWebsocket, AppWebsocket, AuthorizeSigningCredentialsPayload, CellId, CellInfo,
-    ClientAgentSigner, DynAgentSigner, ExternIO, LairAgentSigner, WebsocketConfig, ZomeCallTarget,
+    AdminWebsocket, AppAuthenticationToken, AppWebsocket, AuthorizeSigningCredentialsPayload,
+    CallZomeOptions, CellId, CellInfo, ClientAgentSigner, ClonedCell, DynAgentSigner, ExternIO,
+    GrantedFunctions, SigningCredentials, WebsocketConfig, ZomeCallTarget,
 };
+use holochain_types::app::{
+    AppStatus, CreateCloneCellPayload, DisableCloneCellPayload, EnableCloneCellPayload,
+};
+use holochain_types::prelude::{
+    AgentPubKey, CapAccess, CapAccessInfo, CapSecret, CloneCellId, DesensitizedZomeCallCapGrant,
+    ZomeCallCapGrant,
+};
+use holochain_types::signal::Signal;
 use lair_keystore_api::dependencies::sodoken::LockedArray;
 use lair_keystore_api::dependencies::url::Url;
 use lair_keystore_api::ipc_keystore_connect;
 use lair_keystore_api::types::SharedLockedArray;
+use lair_keystore_api::LairClient;
 use serde::de::DeserializeOwned;
-use std::net::Ipv4Addr;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tracing::{debug, info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{debug, debug_span, info, warn, Instrument};
 
 /// Lair connection details that make [`Ham::connect`] sign zome calls as the
 /// cell's own agent key (the implicit `ChainAuthor` grant) instead of
@@ -41,11 +58,36 @@ impl std::fmt::Debug for LairSigning {
     }
 }
 
+/// A pre-issued app auth token that makes [`Ham::connect`] skip the admin
+/// interface entirely. Built by [`HamConfig::app_only`] /
+/// [`HamConfig::with_app_token`].
+#[derive(Clone)]
+pub struct AppToken(pub AppAuthenticationToken);
+
+impl std::fmt::Debug for AppToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never render the token.
+        f.write_str("AppToken(<redacted>)")
+    }
+}
+
+/// Host both interfaces default to — the loopback the conductor binds by
+/// default.
+const DEFAULT_HOST: &str = "127.0.0.1";
+
 /// Configuration for establishing a new [`Ham`] connection.
 #[derive(Debug, Clone)]
 pub struct HamConfig {
+    /// Host of the conductor's admin interface: an IPv4 or IPv6 literal
+    /// (brackets optional) or a hostname, resolved at connect time. Defaults
+    /// to `127.0.0.1`.
+    pub admin_host: String,
     /// Admin websocket port (conductor `admin_interfaces` entry).
     pub admin_port: u16,
+    /// Host of the conductor's app interfaces, in the same forms as
+    /// [`HamConfig::admin_host`]. Independent of it, since a sidecar may reach
+    /// the two interfaces by different names. Defaults to `127.0.0.1`.
+    pub app_host: String,
     /// App websocket port to attach if no existing app interface is present.
     pub app_port: u16,
     /// Installed app id (`--installed-app-id`).
@@ -55,6 +97,11 @@ pub struct HamConfig {
     /// indefinitely. Daemons typically set 60-120 seconds; one-shots can
     /// choose a shorter budget tied to their cron cadence.
     pub request_timeout_secs: u64,
+    /// Per-function overrides of `request_timeout_secs`, keyed by
+    /// `(zome_name, fn_name)`, so a quick read and a bulk commit on the same
+    /// connection can have different budgets. An explicit
+    /// [`Ham::call_zome_with_timeout`] wins over this table.
+    pub call_timeouts_secs: BTreeMap<(String, String), u64>,
     /// Skip `list_app_interfaces` discovery and always attach a fresh
     /// `AllowedOrigins::Any` app interface. Needed when the conductor has
     /// pre-existing unrestricted app interfaces whose token/origin state
@@ -64,6 +111,38 @@ pub struct HamConfig {
     /// When set, [`Ham::connect`] signs via lair (the cell's own agent key, no
     /// cap grant) instead of authorizing a throwaway signing key on chain.
     pub lair: Option<LairSigning>,
+    /// When set, [`Ham::connect`] opens the app websocket at
+    /// `app_host`:`app_port` with this token and never touches the admin
+    /// interface: no discovery, no attach, no token issue, no grant. Signing
+    /// then needs [`HamConfig::lair`] or [`HamConfig::signing_credentials`].
+    pub app_token: Option<AppToken>,
+    /// Signing credentials authorized ahead of time, per cell. A cell listed
+    /// here is signed with these instead of a freshly authorized throwaway
+    /// key, so no cap grant is committed for it. Ignored when
+    /// [`HamConfig::lair`] is set.
+    pub signing_credentials: Vec<(CellId, Arc<SigningCredentials>)>,
+    /// Where client-path credentials authorized by one connect are kept for
+    /// the next, so a reconnect signs with the existing grant instead of
+    /// committing another. `None` (the default) authorizes afresh on every
+    /// connect; opt in with [`HamConfig::with_credential_store`] or
+    /// [`HamConfig::with_credential_file`]. Entries in
+    /// [`HamConfig::signing_credentials`] take precedence.
+    pub credential_store: Option<CredentialStore>,
+    /// The `(zome_name, fn_name)` pairs this connection may call. When set,
+    /// the cap grant authorized on the client-signing path is
+    /// `GrantedFunctions::Listed` with exactly these pairs instead of every
+    /// function in the cell, and [`Ham::call_zome`] (and friends) refuse any
+    /// other function locally with [`HamError::FunctionNotAllowed`], before
+    /// any signing or conductor work. `None` (the default) allows everything.
+    ///
+    /// Credentials reused from [`HamConfig::credential_store`] or
+    /// [`HamConfig::signing_credentials`] keep whatever scope they were
+    /// granted with; the local check still applies.
+    pub allowed_functions: Option<BTreeSet<(String, String)>>,
+    /// Signals each [`Ham::subscribe_signals`] stream buffers before the
+    /// oldest are dropped for it and it reports
+    /// [`SignalError::Lagged`](crate::signals::SignalError::Lagged).
+    pub signal_buffer: usize,
 }
 
 impl HamConfig {
@@ -72,21 +151,169 @@ impl HamConfig {
     /// chained in future releases without breaking callers.
     pub fn new(admin_port: u16, app_port: u16, app_id: impl Into<String>) -> Self {
         Self {
+            admin_host: DEFAULT_HOST.to_string(),
             admin_port,
+            app_host: DEFAULT_HOST.to_string(),
             app_port,
             app_id: app_id.into(),
             request_timeout_secs: 120,
+            call_timeouts_secs: BTreeMap::new(),
             force_fresh_attach: false,
             lair: None,
+            app_token: None,
+            signing_credentials: Vec::new(),
+            credential_store: None,
+            allowed_functions: None,
+            signal_buffer: 1024,
         }
     }
 
+    /// Build a [`HamConfig`] that connects with a pre-issued app auth token
+    /// and never opens the admin interface, for services that must run
+    /// without admin rights. `app_port` is the app interface to connect to
+    /// (no attach happens); `admin_port` is left unused at `0`.
+    ///
+    /// A reconnect reuses the same token, so issue it with `single_use:
+    /// false` and `expiry_seconds: 0`. Pair with lair signing or
+    /// [`HamConfig::with_signing_credentials`].
+    pub fn app_only(
+        app_port: u16,
+        app_id: impl Into<String>,
+        token: AppAuthenticationToken,
+    ) -> Self {
+        Self::new(0, app_port, app_id).with_app_token(token)
+    }
+
+    /// Connect with a pre-issued app auth token instead of via the admin
+    /// interface (see [`HamConfig::app_only`]).
+    pub fn with_app_token(mut self, token: AppAuthenticationToken) -> Self {
+        self.app_token = Some(AppToken(token));
+        self
+    }
+
+    /// Sign zome calls to `cell_id` with credentials authorized ahead of time
+    /// (e.g. by a provisioning step holding admin rights), instead of
+    /// authorizing a throwaway key on every connect.
+    pub fn with_signing_credentials(
+        mut self,
+        cell_id: CellId,
+        credentials: SigningCredentials,
+    ) -> Self {
+        self.signing_credentials.retain(|(c, _)| *c != cell_id);
+        self.signing_credentials
+            .push((cell_id, Arc::new(credentials)));
+        self
+    }
+
+    /// Keep authorized client-signing credentials in `store` for reuse by later
+    /// connects — [`CredentialStore::new`] for reconnects within this process,
+    /// or one shared with another config or pre-loaded by the caller.
+    pub fn with_credential_store(mut self, store: CredentialStore) -> Self {
+        self.credential_store = Some(store);
+        self
+    }
+
+    /// Keep authorized client-signing credentials in the passphrase-encrypted
+    /// file at `path`, so grants are reused across process restarts too (see
+    /// [`CredentialStore::open_encrypted`]).
+    pub fn with_credential_file(self, path: impl Into<PathBuf>, passphrase: &[u8]) -> Result<Self> {
+        Ok(self.with_credential_store(CredentialStore::open_encrypted(path, passphrase)?))
+    }
+
+    /// Authorize a fresh signing key on every connect (the default), dropping
+    /// any store set earlier. Each connect then commits a cap grant per cell.
+    pub fn without_credential_reuse(mut self) -> Self {
+        self.credential_store = None;
+        self
+    }
+
+    /// Reach both the admin and app interfaces at `host` (see
+    /// [`HamConfig::admin_host`] for accepted forms).
+    pub fn with_host(self, host: impl Into<String>) -> Self {
+        let host = host.into();
+        self.with_admin_host(host.clone()).with_app_host(host)
+    }
+
+    /// Reach the admin interface at `host`.
+    pub fn with_admin_host(mut self, host: impl Into<String>) -> Self {
+        self.admin_host = host.into();
+        self
+    }
+
+    /// Reach the app interface at `host`.
+    pub fn with_app_host(mut self, host: impl Into<String>) -> Self {
+        self.app_host = host.into();
+        self
+    }
+
+    /// Override how many signals each signal subscriber may fall behind by.
+    pub fn with_signal_buffer(mut self, signals: usize) -> Self {
+        self.signal_buffer = signals;
+        self
+    }
+
     /// Override the per-request timeout (seconds).
     pub fn with_request_timeout_secs(mut self, secs: u64) -> Self {
         self.request_timeout_secs = secs;
         self
     }
 
+    /// Override the per-request timeout (seconds) for calls to one zome
+    /// function.
+    pub fn with_call_timeout_secs(
+        mut self,
+        zome_name: impl Into<String>,
+        fn_name: impl Into<String>,
+        secs: u64,
+    ) -> Self {
+        self.call_timeouts_secs
+            .insert((zome_name.into(), fn_name.into()), secs);
+        self
+    }
+
+    /// The timeout for calls to `zome_name`/`fn_name` from
+    /// `call_timeouts_secs`, or `None` to use the connection-wide
+    /// `request_timeout_secs`.
+    pub fn call_timeout(&self, zome_name: &str, fn_name: &str) -> Option<Duration> {
+        self.call_timeouts_secs
+            .get(&(zome_name.to_string(), fn_name.to_string()))
+            .map(|secs| Duration::from_secs(*secs))
+    }
+
+    /// Add `zome_name`/`fn_name` to [`HamConfig::allowed_functions`], scoping
+    /// the signing grant to the listed functions from the first call on.
+    pub fn with_allowed_function(
+        mut self,
+        zome_name: impl Into<String>,
+        fn_name: impl Into<String>,
+    ) -> Self {
+        self.allowed_functions
+            .get_or_insert_with(BTreeSet::new)
+            .insert((zome_name.into(), fn_name.into()));
+        self
+    }
+
+    /// Whether [`HamConfig::allowed_functions`] lets this connection call
+    /// `zome_name`/`fn_name`.
+    pub fn allows_call(&self, zome_name: &str, fn_name: &str) -> bool {
+        self.allowed_functions
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&(zome_name.to_string(), fn_name.to_string())))
+    }
+
+    /// The functions a newly authorized signing key is granted: the
+    /// [`HamConfig::allowed_functions`] list, or `None` for all of them.
+    fn granted_functions(&self) -> Option<GrantedFunctions> {
+        self.allowed_functions.as_ref().map(|allowed| {
+            GrantedFunctions::Listed(
+                allowed
+                    .iter()
+                    .map(|(zome, func)| (zome.as_str().into(), func.as_str().into()))
+                    .collect(),
+            )
+        })
+    }
+
     /// Always attach a fresh app interface, never reuse an existing one.
     pub fn with_force_fresh_attach(mut self, force: bool) -> Self {
         self.force_fresh_attach = force;
@@ -125,7 +352,7 @@ impl HamConfig {
                 event = "ham.lair_discovery_failed",
                 conductor_config = %conductor_config_path.display(),
                 passphrase_file = %passphrase_file.display(),
-                error = %e,
+                error = %format_args!("{e:#}"),
                 "lair signing unavailable; falling back to client signing (a cap grant is committed per connect)"
             ),
         }
@@ -138,38 +365,64 @@ impl HamConfig {
 /// Construct with [`Ham::connect`]. Use [`Ham::call_zome`] for typed
 /// msgpack zome calls and [`Ham::ping`] as a lightweight health probe before
 /// expensive multi-step cycles.
+///
+/// Every provisioned cell of the app is resolved and given signing
+/// credentials at connect, so one `Ham` can call any role of a multi-role
+/// app; look a role's cell up with [`Ham::cell_id_for_role`].
 pub struct Ham {
     app_connection: AppWebsocket,
     cell_id: CellId,
-    // Held to keep the signer — and, on the lair path, its keystore
-    // connection — alive for the lifetime of the websocket.
-    _signer: DynAgentSigner,
+    cell_ids: BTreeMap<String, CellId>,
+    // Shares its credential map with the websocket's signer, so cells
+    // registered after connect (clones) become callable. Also keeps the lair
+    // keystore connection alive for the lifetime of the websocket.
+    signer: CellSigner,
+    // Clone registration on the client path authorizes a grant over a fresh
+    // admin connection, so the connect parameters are kept.
+    cfg: HamConfig,
+    // Serializes clone registration so concurrent first calls to the same
+    // clone commit one cap grant, not one each.
+    registering: tokio::sync::Mutex<()>,
+    // Signing keys this `Ham` authorized itself — the only grants
+    // `close_and_revoke` revokes. One reused from a shared credential store
+    // may still be signing for another connection.
+    authorized: Mutex<HashSet<AgentPubKey>>,
+    signals: broadcast::Sender<Arc<RawSignal>>,
 }
 
 impl Ham {
     /// Connect to the admin interface, attach an app interface if needed,
-    /// issue an auth token, and open an authenticated app websocket for the
-    /// first provisioned cell of `app_id`.
+    /// issue an auth token, and open an authenticated app websocket with
+    /// signing credentials for every provisioned cell of `app_id`.
     ///
-    /// If `cfg.lair` is set, zome calls are signed with the cell's own agent
+    /// If `cfg.lair` is set, zome calls are signed with the app's own agent
     /// key via lair and **no capability grant is committed**. Otherwise a
-    /// throwaway signing key is authorized on chain (one cap grant per
-    /// connect).
+    /// throwaway signing key is authorized on chain — one cap grant per
+    /// provisioned cell — unless `cfg.credential_store` already holds
+    /// credentials for the cell from an earlier connect.
+    ///
+    /// If `cfg.app_token` is set, the admin interface is never opened — see
+    /// [`HamConfig::app_only`].
     ///
     /// The returned connection honors `cfg.request_timeout_secs` on every
     /// zome call.
     pub async fn connect(cfg: HamConfig) -> Result<Self> {
         info!(
             event = "ham.connecting",
+            admin_host = cfg.admin_host.as_str(),
             admin_port = cfg.admin_port,
+            app_host = cfg.app_host.as_str(),
             app_port = cfg.app_port,
             app_id = cfg.app_id.as_str(),
-            request_timeout_secs = cfg.request_timeout_secs
+            request_timeout_secs = cfg.request_timeout_secs,
+            app_only = cfg.app_token.is_some()
         );
 
-        let admin = AdminWebsocket::connect((Ipv4Addr::LOCALHOST, cfg.admin_port), None)
-            .await
-            .context("Failed to connect to admin interface")?;
+        if let Some(token) = cfg.app_token.clone() {
+            return Self::connect_app_only(cfg, token).await;
+        }
+
+        let admin = connect_admin(&cfg).await?;
 
         let port = if cfg.force_fresh_attach {
             admin
@@ -209,89 +462,164 @@ impl Ham {
             .await
             .context("Failed to issue app auth token")?;
 
-        let mut ws_config = WebsocketConfig::CLIENT_DEFAULT;
-        ws_config.default_request_timeout = Duration::from_secs(cfg.request_timeout_secs);
-        let ws_config = Arc::new(ws_config);
-
-        // The lair path resolves the cell up front: the built-in
-        // `LairAgentSigner` registers the agent key via `add_credentials`
-        // (`&mut self`), which must run before the signer is wrapped in
-        // `Arc<dyn>` and handed to connect — so the cell can't come from
-        // post-connect app info. It commits no cap grant. The client path keeps
-        // the original ordering — connect first, then authorize a throwaway key
-        // on chain — so a *failed* connect commits nothing.
+        // The lair path resolves the cells up front, so the signer is fully
+        // keyed before the websocket's first use. It commits no cap grant. The
+        // client path keeps the original ordering — connect first, then
+        // authorize a throwaway key on chain — so a *failed* connect commits
+        // nothing.
         enum Pending {
-            /// Lair: cell already resolved, signer fully built.
-            Lair(CellId),
+            /// Lair: cells already resolved, signer fully built.
+            Lair(Vec<(String, CellId)>),
             /// Client: authorize the on-chain grant once connect has succeeded.
             Client(ClientAgentSigner),
         }
 
-        let (signer, pending): (DynAgentSigner, Pending) = if let Some(lair) = cfg.lair.as_ref() {
+        let (signer, pending) = if let Some(lair) = cfg.lair.as_ref() {
             // The cell lookup (admin) and the lair connection are independent;
             // run them concurrently — both feed `add_credentials` afterwards.
-            let (cell_id, lair_client) =
-                tokio::try_join!(cell_id_via_admin(&admin, &cfg.app_id), async {
-                    ipc_keystore_connect(lair.connection_url.clone(), lair.passphrase.clone())
-                        .await
-                        .map_err(|e| {
-                            anyhow::anyhow!(
-                                "Failed to connect to lair keystore at {}: {}",
-                                lair.connection_url,
-                                e
-                            )
-                        })
-                },)?;
-            // Key the signer on the app's primary (first) provisioned cell —
-            // the same cell the client path authorizes, and the one every
-            // current (single-role) consumer calls. A multi-role app signing a
-            // non-primary role would need per-cell registration here.
-            let mut signer = LairAgentSigner::new(Arc::new(lair_client));
-            signer.add_credentials(cell_id.clone(), cell_id.agent_pubkey().clone());
-            (Arc::new(signer), Pending::Lair(cell_id))
+            let (cells, lair_client) =
+                tokio::try_join!(cells_via_admin(&admin, &cfg.app_id), connect_lair(lair))?;
+            // Key the signer on every provisioned cell — the same set the
+            // client path authorizes — so any role of a multi-role app can be
+            // called. All cells share the app's agent key, so this is pure
+            // bookkeeping: no extra lair round-trips.
+            let signer = LairCellSigner::new(lair_client);
+            for (_, cell_id) in &cells {
+                signer.add_credentials(cell_id.clone());
+            }
+            (CellSigner::Lair(signer), Pending::Lair(cells))
         } else {
-            let signer = ClientAgentSigner::default();
-            (signer.clone().into(), Pending::Client(signer))
+            let signer = preauthorized_signer(&cfg);
+            (CellSigner::Client(signer.clone()), Pending::Client(signer))
         };
 
         let app_connection = AppWebsocket::connect_with_config(
-            (Ipv4Addr::LOCALHOST, port),
-            ws_config,
+            (unbracket(&cfg.app_host), port),
+            ws_config(&cfg),
             issued_token.token,
-            signer.clone(),
+            DynAgentSigner::from(signer.clone()),
             None,
         )
         .await
         .context("Failed to connect to app interface")?;
 
-        let cell_id = match pending {
-            Pending::Lair(cell_id) => cell_id,
+        let mut authorized = HashSet::new();
+        let cells = match pending {
+            Pending::Lair(cells) => cells,
             Pending::Client(client_signer) => {
-                let cell_id = cell_id_via_app(&app_connection)?;
-                let credentials = admin
-                    .authorize_signing_credentials(AuthorizeSigningCredentialsPayload {
-                        cell_id: cell_id.clone(),
-                        functions: None,
-                    })
-                    .await
-                    .map_err(|e| {
-                        anyhow::anyhow!("Failed to authorize signing credentials: {}", e)
-                    })?;
-                client_signer.add_credentials(cell_id.clone(), credentials);
-                cell_id
+                let cells = cells_via_app(&app_connection)?;
+                for (_, cell_id) in &cells {
+                    if !signer.has_credentials(cell_id) {
+                        authorized
+                            .insert(authorize_cell(&client_signer, &cfg, &admin, cell_id).await?);
+                    }
+                }
+                cells
+            }
+        };
+
+        Ok(Self::connected(app_connection, cells, signer, authorized, cfg).await)
+    }
+
+    /// The [`Ham::connect`] path for a pre-issued app token: open the app
+    /// websocket directly and sign with lair or the pre-authorized
+    /// credentials. With no admin access nothing can be authorized here, so a
+    /// cell left without credentials is logged and stays uncallable.
+    async fn connect_app_only(cfg: HamConfig, token: AppToken) -> Result<Self> {
+        let signer = match cfg.lair.as_ref() {
+            Some(lair) => CellSigner::Lair(LairCellSigner::new(connect_lair(lair).await?)),
+            None => {
+                anyhow::ensure!(
+                    has_preauthorized(&cfg),
+                    "App-token connect needs lair signing or pre-authorized signing credentials"
+                );
+                CellSigner::Client(preauthorized_signer(&cfg))
             }
         };
 
+        let app_connection = AppWebsocket::connect_with_config(
+            (unbracket(&cfg.app_host), cfg.app_port),
+            ws_config(&cfg),
+            token.0,
+            DynAgentSigner::from(signer.clone()),
+            None,
+        )
+        .await
+        .map_err(|e| {
+            HamError::app_token_connect(&cfg.app_id, "Failed to connect to app interface", e)
+        })?;
+
+        // Nothing has been called yet, so the lair signer can be keyed after
+        // connect, from the app info the websocket cached.
+        let cells = cells_via_app(&app_connection)?;
+        for (role_name, cell_id) in &cells {
+            match &signer {
+                CellSigner::Lair(signer) => signer.add_credentials(cell_id.clone()),
+                CellSigner::Client(_) if !signer.has_credentials(cell_id) => warn!(
+                    event = "ham.credentials_missing",
+                    role_name = role_name.as_str(),
+                    cell_id = %cell_id,
+                    "no pre-authorized signing credentials for this cell; its zome calls will fail"
+                ),
+                CellSigner::Client(_) => {}
+            }
+        }
+
+        Ok(Self::connected(app_connection, cells, signer, HashSet::new(), cfg).await)
+    }
+
+    /// Assemble a connected [`Ham`] from the cells `connect` resolved and the
+    /// signing keys it `authorized` for them.
+    async fn connected(
+        app_connection: AppWebsocket,
+        cells: Vec<(String, CellId)>,
+        signer: CellSigner,
+        authorized: HashSet<AgentPubKey>,
+        cfg: HamConfig,
+    ) -> Self {
+        // `provisioned_cells` never returns an empty list, and keeps the app
+        // manifest's role order, so the first entry is the primary cell.
+        let cell_id = cells[0].1.clone();
+
+        // Fan app signals out to `subscribe_signals` streams. The handler
+        // holds a sender for as long as the websocket lives, so the streams
+        // end once the `Ham` (which holds the other) is gone.
+        let (signals, _) = broadcast::channel(cfg.signal_buffer.max(1));
+        let tx = signals.clone();
+        app_connection
+            .on_signal(move |signal| {
+                if let Signal::App {
+                    cell_id,
+                    zome_name,
+                    signal,
+                } = signal
+                {
+                    // No receivers is fine; the signal is just dropped.
+                    let _ = tx.send(Arc::new(RawSignal {
+                        cell_id,
+                        zome_name: zome_name.to_string(),
+                        payload: signal.into_inner(),
+                    }));
+                }
+            })
+            .await;
+
         info!(
             event = "ham.connected",
-            signing = if cfg.lair.is_some() { "lair" } else { "client" }
+            signing = signer.kind(),
+            cells = cells.len()
         );
 
-        Ok(Self {
+        Self {
             app_connection,
             cell_id,
-            _signer: signer,
-        })
+            cell_ids: cells.into_iter().collect(),
+            signer,
+            cfg,
+            registering: tokio::sync::Mutex::new(()),
+            authorized: Mutex::new(authorized),
+            signals,
+        }
     }
 
     /// Call a zome function and decode the msgpack response into `R`.
@@ -306,18 +634,457 @@ impl Ham {
         I: serde::Serialize + std::fmt::Debug,
         R: DeserializeOwned,
     {
-        debug!(event = "ham.call_zome", role_name, zome_name, fn_name);
-        let response = self
+        self.call_zome_encoded(
+            role_name,
+            zome_name,
+            fn_name,
+            ExternIO::encode(payload)?,
+            None,
+        )
+        .await
+    }
+
+    /// [`Ham::call_zome`] with its own deadline, overriding both
+    /// `request_timeout_secs` and `call_timeouts_secs` for this one call. An
+    /// expiry still renders as `"Websocket error: Timeout"`, so
+    /// [`is_request_timeout`](crate::is_request_timeout) classifies it.
+    pub async fn call_zome_with_timeout<I, R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+        timeout: Duration,
+    ) -> Result<R>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        R: DeserializeOwned,
+    {
+        self.call_zome_encoded(
+            role_name,
+            zome_name,
+            fn_name,
+            ExternIO::encode(payload)?,
+            Some(timeout),
+        )
+        .await
+    }
+
+    /// [`Ham::call_zome`] with the payload already msgpack-encoded, so
+    /// [`ManagedHam`](crate::ManagedHam) can resend the same bytes after a
+    /// reconnect.
+    pub(crate) async fn call_zome_encoded<R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: ExternIO,
+        timeout: Option<Duration>,
+    ) -> Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        self.check_allowed(zome_name, fn_name)?;
+        self.call_zome_target(
+            ZomeCallTarget::RoleName(role_name.to_string()),
+            zome_name,
+            fn_name,
+            payload,
+            timeout,
+        )
+        .await
+    }
+
+    /// [`Ham::call_zome`], retried on this same connection per `policy`:
+    /// per-request timeouts and source-chain pressure are retried after their
+    /// cooldowns until the class's budget is spent. Connection errors are
+    /// returned at once — reconnecting is the caller's (or
+    /// [`ManagedHam`](crate::ManagedHam)'s) job — as is anything unclassified.
+    ///
+    /// A retry re-sends the call, so only use this for zome functions that
+    /// are safe to repeat: a timed-out commit may still have landed. The
+    /// cooldowns are plain sleeps; race the call against shutdown if they
+    /// must be interruptible.
+    pub async fn call_zome_with_retry<I, R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+        policy: &RetryPolicy,
+    ) -> Result<R>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        R: DeserializeOwned,
+    {
+        self.call_zome_encoded_with_retry(
+            role_name,
+            zome_name,
+            fn_name,
+            ExternIO::encode(payload)?,
+            policy,
+        )
+        .await
+    }
+
+    /// [`Ham::call_zome_with_retry`] with the payload already encoded.
+    pub(crate) async fn call_zome_encoded_with_retry<R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: ExternIO,
+        policy: &RetryPolicy,
+    ) -> Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        let mut state = RetryState::default();
+        loop {
+            let err = match self
+                .call_zome_encoded(role_name, zome_name, fn_name, payload.clone(), None)
+                .await
+            {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+            let Some(cooldown) = policy.next_cooldown(&err, &mut state) else {
+                return Err(err);
+            };
+            warn!(
+                event = "ham.call_zome.retry",
+                role_name,
+                zome_name,
+                fn_name,
+                timeouts = state.timeouts,
+                pressure = state.pressure,
+                cooldown_ms = cooldown.as_millis() as u64,
+                rule = %classify(&err).rule,
+                error = %format_args!("{err:#}"),
+            );
+            tokio::time::sleep(cooldown).await;
+        }
+    }
+
+    /// Call a zome function on a specific cell — typically a clone from
+    /// [`Ham::create_clone_cell`] or [`Ham::list_clone_cells`] — and decode the
+    /// msgpack response into `R`.
+    ///
+    /// A cell this connection has no credentials for yet (e.g. a clone created
+    /// by another process) is registered first; on the client-signing path
+    /// that commits one cap grant for it.
+    pub async fn call_zome_on_cell<I, R>(
+        &self,
+        cell_id: CellId,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+    ) -> Result<R>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        R: DeserializeOwned,
+    {
+        self.check_allowed(zome_name, fn_name)?;
+        let payload = ExternIO::encode(payload)?;
+        self.ensure_credentials(&cell_id).await?;
+        self.call_zome_target(
+            ZomeCallTarget::CellId(cell_id),
+            zome_name,
+            fn_name,
+            payload,
+            None,
+        )
+        .await
+    }
+
+    /// Refuse a call `cfg.allowed_functions` doesn't list, before any
+    /// credential or conductor work is done for it.
+    fn check_allowed(&self, zome_name: &str, fn_name: &str) -> Result<()> {
+        if self.cfg.allows_call(zome_name, fn_name) {
+            return Ok(());
+        }
+        Err(HamError::FunctionNotAllowed {
+            zome_name: zome_name.to_string(),
+            fn_name: fn_name.to_string(),
+        }
+        .into())
+    }
+
+    /// Every zome call funnels through here. `timeout` overrides the
+    /// `call_timeouts_secs` entry, which overrides the connection default.
+    ///
+    /// Runs the call in a `ham.call_zome` span, emits `ham.call_zome.done`
+    /// when it finishes, and adds a [`ZomeCall`] context to any error.
+    async fn call_zome_target<R>(
+        &self,
+        target: ZomeCallTarget,
+        zome_name: &str,
+        fn_name: &str,
+        payload: ExternIO,
+        timeout: Option<Duration>,
+    ) -> Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        let call = ZomeCall {
+            request_id: NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+            target: CallTarget {
+                role_name: match &target {
+                    ZomeCallTarget::RoleName(role_name) => Some(role_name.clone()),
+                    _ => None,
+                },
+                cell_id: match &target {
+                    ZomeCallTarget::RoleName(role_name) => {
+                        self.cell_id_for_role(role_name).cloned()
+                    }
+                    ZomeCallTarget::CellId(cell_id) => Some(cell_id.clone()),
+                    _ => None,
+                },
+                zome_name: zome_name.to_string(),
+                fn_name: fn_name.to_string(),
+            },
+            elapsed: Duration::ZERO,
+        };
+        let span = debug_span!(
+            "ham.call_zome",
+            request_id = call.request_id,
+            role_name = call.target.role_name.as_deref(),
+            cell_id = call.target.cell_id.as_ref().map(tracing::field::display),
+            zome_name,
+            fn_name,
+        );
+        let started = Instant::now();
+        let result = self
+            .call_zome_once(target, &call.target, payload, timeout)
+            .instrument(span.clone())
+            .await;
+        let call = ZomeCall {
+            elapsed: started.elapsed(),
+            ..call
+        };
+        let latency_ms = call.elapsed.as_millis() as u64;
+        span.in_scope(|| match &result {
+            Ok(_) => debug!(event = "ham.call_zome.done", latency_ms, outcome = "ok"),
+            Err(e) => warn!(
+                event = "ham.call_zome.done",
+                latency_ms,
+                outcome = "error",
+                rule = %classify(e).rule,
+                error = %format_args!("{e:#}"),
+            ),
+        });
+        result.map_err(|e| e.context(call))
+    }
+
+    /// The call itself, for [`Ham::call_zome_target`].
+    async fn call_zome_once<R>(
+        &self,
+        target: ZomeCallTarget,
+        call: &CallTarget,
+        payload: ExternIO,
+        timeout: Option<Duration>,
+    ) -> Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        let (zome_name, fn_name) = (call.zome_name.as_str(), call.fn_name.as_str());
+        let options = CallZomeOptions {
+            timeout: timeout.or_else(|| self.cfg.call_timeout(zome_name, fn_name)),
+        };
+        // A grant reused from the credential store may have been revoked
+        // since it was stored; keep the payload to re-send once under a new one.
+        let stored = call.cell_id.as_ref().and_then(|cell_id| {
+            let signed_with = self.stored_grant(cell_id)?;
+            Some((cell_id, signed_with, payload.clone()))
+        });
+        let result = self
             .app_connection
-            .call_zome(
-                ZomeCallTarget::RoleName(role_name.to_string()),
+            .call_zome_with_options(
+                target,
                 zome_name.into(),
                 fn_name.into(),
-                ExternIO::encode(payload)?,
+                payload,
+                options.clone(),
             )
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to call zome: {}", e))?;
-        rmp_serde::from_slice(&response.0).context("Failed to deserialize response")
+            .map_err(|e| HamError::conductor("Failed to call zome", e).for_call(call.clone()));
+        let response = match (result, stored) {
+            (Err(e), Some((cell_id, signed_with, payload))) if e.is_unauthorized() => {
+                if let Err(reauth) = self.replace_stored_grant(cell_id, &signed_with).await {
+                    warn!(
+                        event = "ham.credentials_refresh_failed",
+                        cell_id = %cell_id,
+                        error = %format_args!("{reauth:#}"),
+                        "stored signing grant refused and no replacement could be authorized"
+                    );
+                    return Err(e.into());
+                }
+                self.app_connection
+                    .call_zome_with_options(
+                        ZomeCallTarget::CellId(cell_id.clone()),
+                        zome_name.into(),
+                        fn_name.into(),
+                        payload,
+                        options,
+                    )
+                    .await
+                    .map_err(|e| {
+                        HamError::conductor("Failed to call zome", e).for_call(call.clone())
+                    })?
+            }
+            (result, _) => result?,
+        };
+        Ok(rmp_serde::from_slice(&response.0).map_err(|source| {
+            HamError::deserialize::<R>("Failed to deserialize response", &response.0, source)
+        })?)
+    }
+
+    /// Create a clone cell and register signing credentials for it, so it can
+    /// be called straight away with [`Ham::call_zome_on_cell`].
+    pub async fn create_clone_cell(&self, payload: CreateCloneCellPayload) -> Result<ClonedCell> {
+        let role_name = payload.role_name.clone();
+        let cell = self
+            .app_connection
+            .create_clone_cell(payload)
+            .await
+            .map_err(|e| {
+                HamError::conductor(format!("Failed to create clone cell of `{role_name}`"), e)
+            })?;
+        self.ensure_credentials(&cell.cell_id).await?;
+        info!(event = "ham.clone_cell.created", role_name, clone_id = %cell.clone_id);
+        Ok(cell)
+    }
+
+    /// Re-enable a disabled clone cell and make sure this connection has
+    /// signing credentials for it.
+    pub async fn enable_clone_cell(&self, clone_cell_id: CloneCellId) -> Result<ClonedCell> {
+        let cell = self
+            .app_connection
+            .enable_clone_cell(EnableCloneCellPayload { clone_cell_id })
+            .await
+            .map_err(|e| HamError::conductor("Failed to enable clone cell", e))?;
+        self.ensure_credentials(&cell.cell_id).await?;
+        info!(event = "ham.clone_cell.enabled", clone_id = %cell.clone_id);
+        Ok(cell)
+    }
+
+    /// Disable a clone cell. Its credentials stay registered, so re-enabling
+    /// it on this connection commits no new cap grant.
+    pub async fn disable_clone_cell(&self, clone_cell_id: CloneCellId) -> Result<()> {
+        self.app_connection
+            .disable_clone_cell(DisableCloneCellPayload { clone_cell_id })
+            .await
+            .map_err(|e| HamError::conductor("Failed to disable clone cell", e))?;
+        info!(event = "ham.clone_cell.disabled");
+        Ok(())
+    }
+
+    /// List the clone cells — enabled and disabled — of `role_name`, from
+    /// fresh app info.
+    pub async fn list_clone_cells(&self, role_name: &str) -> Result<Vec<ClonedCell>> {
+        let app_info = self
+            .app_info()
+            .await?
+            .ok_or_else(|| HamError::NotInstalled {
+                app_id: self.cfg.app_id.clone(),
+            })?;
+        Ok(app_info
+            .cell_info
+            .get(role_name)
+            .into_iter()
+            .flatten()
+            .filter_map(|cell| match cell {
+                CellInfo::Cloned(c) => Some(c.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Register signing credentials for `cell_id` unless it already has them.
+    /// The lair path only records the agent key; the client path authorizes a
+    /// throwaway key over a fresh admin connection.
+    async fn ensure_credentials(&self, cell_id: &CellId) -> Result<()> {
+        let client_signer = match &self.signer {
+            CellSigner::Lair(signer) => {
+                signer.add_credentials(cell_id.clone());
+                return Ok(());
+            }
+            CellSigner::Client(signer) => signer,
+        };
+        if self.signer.has_credentials(cell_id) {
+            return Ok(());
+        }
+        anyhow::ensure!(
+            self.cfg.app_token.is_none(),
+            "No signing credentials for {cell_id}, and an app-token connection has no admin access to authorize them"
+        );
+        let _guard = self.registering.lock().await;
+        if self.signer.has_credentials(cell_id) {
+            return Ok(());
+        }
+        let admin = connect_admin(&self.cfg).await?;
+        let signing_key = authorize_cell(client_signer, &self.cfg, &admin, cell_id).await?;
+        self.authorized_keys().insert(signing_key);
+        Ok(())
+    }
+
+    /// The signing key calls to `cell_id` go out under, if it is a grant
+    /// reused from `cfg.credential_store` that this connection can replace:
+    /// the client path with admin access. `None` for caller-supplied
+    /// credentials, which the store entry doesn't match.
+    fn stored_grant(&self, cell_id: &CellId) -> Option<AgentPubKey> {
+        let stored = self.cfg.credential_store.as_ref()?.signing_key(cell_id)?;
+        if self.cfg.app_token.is_some() {
+            return None;
+        }
+        let (signing_key, _) = self.signer.client_grant(cell_id)?;
+        (signing_key == stored).then_some(signing_key)
+    }
+
+    /// Evict the stored grant `signed_with`, which the conductor just refused
+    /// for `cell_id`, and authorize a replacement. If a concurrent call already
+    /// replaced it, there is nothing left to do.
+    async fn replace_stored_grant(
+        &self,
+        cell_id: &CellId,
+        signed_with: &AgentPubKey,
+    ) -> Result<()> {
+        let CellSigner::Client(client_signer) = &self.signer else {
+            return Ok(());
+        };
+        let _guard = self.registering.lock().await;
+        if self
+            .signer
+            .client_grant(cell_id)
+            .is_some_and(|(signing_key, _)| signing_key != *signed_with)
+        {
+            return Ok(());
+        }
+        warn!(
+            event = "ham.credentials_evicted",
+            cell_id = %cell_id,
+            "stored signing grant refused as unauthorized; authorizing a new one"
+        );
+        if let Some(store) = &self.cfg.credential_store {
+            if let Err(e) = store.remove(cell_id) {
+                warn!(
+                    event = "ham.credentials_persist_failed",
+                    cell_id = %cell_id,
+                    error = %format_args!("{e:#}"),
+                    "evicted signing credentials still in the credential file"
+                );
+            }
+        }
+        let admin = connect_admin(&self.cfg).await?;
+        let signing_key = authorize_cell(client_signer, &self.cfg, &admin, cell_id).await?;
+        self.authorized_keys().insert(signing_key);
+        Ok(())
+    }
+
+    fn authorized_keys(&self) -> std::sync::MutexGuard<'_, HashSet<AgentPubKey>> {
+        self.authorized
+            .lock()
+            .expect("authorized keys lock poisoned")
     }
 
     /// Round-trip probe that surfaces a dead websocket immediately. Uses
@@ -327,54 +1094,313 @@ impl Ham {
         self.app_connection
             .app_info()
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to probe app_info: {}", e))?;
+            .map_err(|e| HamError::conductor("Failed to probe app_info", e))?;
         Ok(())
     }
 
     /// Fetch fresh app info from the conductor.
     pub async fn app_info(&self) -> Result<Option<holochain_client::AppInfo>> {
-        self.app_connection
+        Ok(self
+            .app_connection
             .app_info()
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to get app info: {}", e))
+            .map_err(|e| HamError::conductor("Failed to get app info", e))?)
+    }
+
+    /// Subscribe to the app signals this connection receives, decoded from
+    /// msgpack into `T`. Every call returns an independent stream with its
+    /// own buffer; narrow it with [`SignalStream::for_cell`] /
+    /// [`SignalStream::for_zome`]. Only signals emitted after subscribing are
+    /// seen.
+    pub fn subscribe_signals<T: DeserializeOwned>(&self) -> SignalStream<T> {
+        SignalStream::new(self.signals.subscribe())
     }
 
     /// The [`CellId`] of the first provisioned cell, captured at connect time.
     pub fn cell_id(&self) -> &CellId {
         &self.cell_id
     }
+
+    /// The provisioned [`CellId`] for `role_name`, captured at connect time.
+    /// `None` if the app has no such role, or the role has no provisioned
+    /// cell.
+    pub fn cell_id_for_role(&self, role_name: &str) -> Option<&CellId> {
+        self.cell_ids.get(role_name)
+    }
+
+    /// Every provisioned cell, keyed by role name.
+    pub fn cell_ids(&self) -> &BTreeMap<String, CellId> {
+        &self.cell_ids
+    }
+
+    /// Close the app websocket and emit `ham.closed`. Best-effort: the close
+    /// frame is sent by a task the websocket spawns on drop, which this
+    /// doesn't wait for, so if the runtime shuts down first the conductor sees
+    /// a dropped socket instead — which it cleans up just the same.
+    pub async fn close(self) {
+        drop(self.app_connection);
+        info!(event = "ham.closed", revoked = 0);
+    }
+
+    /// [`Ham::close`], after revoking the cap grants this connection
+    /// authorized itself on the client-signing path, so a CLI run leaves no
+    /// live grant behind. Each revoked grant is also removed from
+    /// [`HamConfig::credential_store`]; an entry whose revoke fails stays.
+    /// Grants reused from the store were authorized by another connection,
+    /// which may still sign with them, and credentials supplied via
+    /// [`HamConfig::signing_credentials`] belong to the caller; both are left
+    /// alone, as is the lair path, which has no grant.
+    ///
+    /// Needs the admin interface, so on the client-signing path it errors in
+    /// app-token mode. The socket is closed whether or not revocation
+    /// succeeds.
+    pub async fn close_and_revoke(self) -> Result<()> {
+        let revoked = self.revoke_grants().await;
+        drop(self.app_connection);
+        let revoked = revoked?;
+        info!(event = "ham.closed", revoked);
+        Ok(())
+    }
+
+    /// Revoke every grant [`Ham::close_and_revoke`] is responsible for;
+    /// returns how many were revoked.
+    async fn revoke_grants(&self) -> Result<usize> {
+        if matches!(self.signer, CellSigner::Lair(_)) {
+            return Ok(0);
+        }
+        anyhow::ensure!(
+            self.cfg.app_token.is_none(),
+            "Cannot revoke signing grants: an app-token connection has no admin access"
+        );
+        let admin = connect_admin(&self.cfg).await?;
+        let grants = admin
+            .list_capability_grants(self.cfg.app_id.clone(), false)
+            .await
+            .map_err(|e| HamError::conductor("Failed to list capability grants", e))?;
+
+        let mut revoked = 0;
+        for (cell_id, infos) in grants.0 {
+            let Some((signing_key, cap_secret)) = self.signer.client_grant(&cell_id) else {
+                continue;
+            };
+            if !self.authorized_keys().contains(&signing_key) {
+                continue;
+            }
+            let ours = signing_grant_access(signing_key.clone(), cap_secret);
+            let mut revoked_here = 0;
+            for info in infos {
+                if info.cap_grant.tag != SIGNING_GRANT_TAG || info.cap_grant.access != ours {
+                    continue;
+                }
+                admin
+                    .revoke_zome_call_capability(cell_id.clone(), info.action_hash)
+                    .await
+                    .map_err(|e| {
+                        HamError::conductor(
+                            format!("Failed to revoke signing grant on {cell_id}"),
+                            e,
+                        )
+                    })?;
+                revoked_here += 1;
+            }
+            revoked += revoked_here;
+            if revoked_here == 0 {
+                continue;
+            }
+            if let Some(store) = &self.cfg.credential_store {
+                // Another connection may have stored a newer grant since.
+                if store.signing_key(&cell_id) == Some(signing_key) {
+                    store.remove(&cell_id)?;
+                }
+            }
+        }
+        Ok(revoked)
+    }
+}
+
+/// Source of [`ZomeCall::request_id`]: unique per process, in call order.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Tag `AdminWebsocket::authorize_signing_credentials` gives the grants it
+/// commits.
+const SIGNING_GRANT_TAG: &str = "zome-call-signing-key";
+
+/// The access of the grant `authorize_signing_credentials` commits for
+/// `signing_key`. Grants are listed without their secret, so this is what
+/// [`Ham::close_and_revoke`] matches its own grants by.
+fn signing_grant_access(signing_key: AgentPubKey, cap_secret: CapSecret) -> CapAccessInfo {
+    DesensitizedZomeCallCapGrant::from(ZomeCallCapGrant::new(
+        SIGNING_GRANT_TAG.to_string(),
+        CapAccess::Assigned {
+            secret: cap_secret,
+            assignees: BTreeSet::from([signing_key]),
+        },
+        GrantedFunctions::All,
+    ))
+    .access
+}
+
+/// The app websocket config for `cfg`: the client defaults with
+/// `cfg.request_timeout_secs` as the per-request timeout.
+fn ws_config(cfg: &HamConfig) -> Arc<WebsocketConfig> {
+    let mut ws_config = WebsocketConfig::CLIENT_DEFAULT;
+    ws_config.default_request_timeout = Duration::from_secs(cfg.request_timeout_secs);
+    Arc::new(ws_config)
+}
+
+/// Open the lair keystore connection the lair signer signs through.
+async fn connect_lair(lair: &LairSigning) -> Result<LairClient> {
+    ipc_keystore_connect(lair.connection_url.clone(), lair.passphrase.clone())
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to connect to lair keystore at {}: {}",
+                lair.connection_url,
+                e
+            )
+        })
+}
+
+/// A client signer seeded with `cfg.credential_store`, then
+/// `cfg.signing_credentials` (so explicit entries win). `SigningCredentials`
+/// isn't `Clone`, so each connect gets its own field-by-field copy.
+fn preauthorized_signer(cfg: &HamConfig) -> ClientAgentSigner {
+    let signer = ClientAgentSigner::default();
+    if let Some(store) = &cfg.credential_store {
+        for (cell_id, credentials) in store.entries() {
+            signer.add_credentials(cell_id, credentials);
+        }
+    }
+    for (cell_id, credentials) in &cfg.signing_credentials {
+        signer.add_credentials(cell_id.clone(), copy_credentials(credentials));
+    }
+    signer
 }
 
-/// Reduce an [`AppInfo`] to the [`CellId`] of its first provisioned cell.
-fn first_provisioned_cell(app_info: &holochain_client::AppInfo) -> Result<CellId> {
-    let cells = app_info
+/// Whether `cfg` brings any client-signing credentials of its own.
+fn has_preauthorized(cfg: &HamConfig) -> bool {
+    !cfg.signing_credentials.is_empty()
+        || cfg
+            .credential_store
+            .as_ref()
+            .is_some_and(|store| !store.is_empty())
+}
+
+/// Open the admin websocket at `cfg.admin_host`:`cfg.admin_port`.
+async fn connect_admin(cfg: &HamConfig) -> Result<AdminWebsocket> {
+    AdminWebsocket::connect((unbracket(&cfg.admin_host), cfg.admin_port), None)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to connect to admin interface at {}:{}",
+                cfg.admin_host, cfg.admin_port
+            )
+        })
+}
+
+/// Strip the brackets from an IPv6 literal written URL-style (`[::1]`), which
+/// `ToSocketAddrs` only accepts bare. Anything else passes through unchanged.
+fn unbracket(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Authorize a throwaway signing key for `cell_id` over `admin` — committing
+/// a cap grant scoped to `cfg.allowed_functions` — and hand its credentials to
+/// `signer` and, for reuse by later connects, to `cfg.credential_store`.
+/// Returns the new signing key.
+async fn authorize_cell(
+    signer: &ClientAgentSigner,
+    cfg: &HamConfig,
+    admin: &AdminWebsocket,
+    cell_id: &CellId,
+) -> Result<AgentPubKey> {
+    let credentials = admin
+        .authorize_signing_credentials(AuthorizeSigningCredentialsPayload {
+            cell_id: cell_id.clone(),
+            functions: cfg.granted_functions(),
+        })
+        .await
+        .map_err(|e| {
+            HamError::conductor(
+                format!("Failed to authorize signing credentials for {cell_id}"),
+                e,
+            )
+        })?;
+    if let Some(store) = &cfg.credential_store {
+        // The grant is committed either way; failing to persist it only costs
+        // a fresh grant on the next restart, so it shouldn't fail the connect.
+        if let Err(e) = store.insert(cell_id.clone(), &credentials) {
+            warn!(
+                event = "ham.credentials_persist_failed",
+                cell_id = %cell_id,
+                error = %format_args!("{e:#}"),
+                "signing credentials not saved; the next restart authorizes a new grant"
+            );
+        }
+    }
+    let signing_key = credentials.signing_agent_key.clone();
+    signer.add_credentials(cell_id.clone(), credentials);
+    Ok(signing_key)
+}
+
+/// Reduce an [`AppInfo`] to the [`CellId`] of every role's provisioned cell,
+/// in manifest role order. Roles without one (e.g. deferred provisioning)
+/// are skipped; an app with no provisioned cell at all is an error.
+///
+/// [`AppInfo`]: holochain_client::AppInfo
+fn provisioned_cells(app_info: &holochain_client::AppInfo) -> Result<Vec<(String, CellId)>> {
+    let app_id = &app_info.installed_app_id;
+    // `AwaitingRestore` settles on its own; every other non-enabled status
+    // needs an operator.
+    if !matches!(
+        app_info.status,
+        AppStatus::Enabled | AppStatus::AwaitingRestore
+    ) {
+        return Err(HamError::AppDisabled {
+            app_id: app_id.clone(),
+            status: app_info.status.clone(),
+        }
+        .into());
+    }
+    // The conductor lists a role's provisioned cell first, ahead of its clones.
+    let cells: Vec<_> = app_info
         .cell_info
-        .values()
-        .next()
-        .context("No cells found in app")?;
-    match cells.first().context("Empty cell list")? {
-        CellInfo::Provisioned(c) => Ok(c.cell_id.clone()),
-        _ => anyhow::bail!("Invalid cell type: expected Provisioned"),
+        .iter()
+        .filter_map(|(role_name, cells)| match cells.first() {
+            Some(CellInfo::Provisioned(c)) => Some((role_name.clone(), c.cell_id.clone())),
+            _ => None,
+        })
+        .collect();
+    if cells.is_empty() {
+        return Err(HamError::NoProvisionedCells {
+            app_id: app_id.clone(),
+        }
+        .into());
     }
+    Ok(cells)
 }
 
-/// Resolve `app_id`'s first provisioned [`CellId`] via the admin interface —
-/// used by the lair path, which needs the cell before the app websocket opens.
-async fn cell_id_via_admin(admin: &AdminWebsocket, app_id: &str) -> Result<CellId> {
+/// Resolve every provisioned [`CellId`] of `app_id` via the admin interface —
+/// used by the lair path, which needs the cells before the app websocket opens.
+async fn cells_via_admin(admin: &AdminWebsocket, app_id: &str) -> Result<Vec<(String, CellId)>> {
     let app_info = admin
         .list_apps(None)
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to list apps: {}", e))?
+        .map_err(|e| HamError::conductor("Failed to list apps", e))?
         .into_iter()
         .find(|app| app.installed_app_id == app_id)
-        .with_context(|| format!("App `{app_id}` not installed"))?;
-    first_provisioned_cell(&app_info)
+        .ok_or_else(|| HamError::NotInstalled {
+            app_id: app_id.to_string(),
+        })?;
+    provisioned_cells(&app_info)
 }
 
-/// Resolve the first provisioned [`CellId`] from the app info the websocket
+/// Resolve every provisioned [`CellId`] from the app info the websocket
 /// cached at connect — used by the client path, with no extra round-trip.
-fn cell_id_via_app(app: &AppWebsocket) -> Result<CellId> {
-    first_provisioned_cell(app.cached_app_info())
+fn cells_via_app(app: &AppWebsocket) -> Result<Vec<(String, CellId)>> {
+    provisioned_cells(app.cached_app_info())
 }
 
 /// Read the lair connection URL + passphrase from the conductor's on-disk
@@ -443,7 +1469,11 @@ fn strip_passphrase(mut bytes: Vec<u8>) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_connection_url, strip_passphrase};
+    use super::{
+        parse_connection_url, signing_grant_access, strip_passphrase, unbracket, AgentPubKey,
+        CapSecret, GrantedFunctions, HamConfig,
+    };
+    use std::time::Duration;
 
     #[test]
     fn parse_connection_url_reads_lair_server_url() {
@@ -484,6 +1514,77 @@ data_root_path: /var/lib/holochain/data
         assert!(parse_connection_url(cfg).is_err());
     }
 
+    #[test]
+    fn call_timeout_looks_up_zome_and_fn_together() {
+        let cfg = HamConfig::new(30000, 30001, "app")
+            .with_call_timeout_secs("ledger", "commit_batch", 300)
+            .with_call_timeout_secs("ledger", "get_balance", 5);
+        assert_eq!(
+            cfg.call_timeout("ledger", "commit_batch"),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            cfg.call_timeout("ledger", "get_balance"),
+            Some(Duration::from_secs(5))
+        );
+        // Unlisted: the connection-wide `request_timeout_secs` applies.
+        assert_eq!(cfg.call_timeout("ledger", "get_history"), None);
+        assert_eq!(cfg.call_timeout("pricing", "commit_batch"), None);
+    }
+
+    #[test]
+    fn signing_grant_access_identifies_the_assignee() {
+        let key = AgentPubKey::from_raw_32(vec![1; 32]);
+        let other = AgentPubKey::from_raw_32(vec![2; 32]);
+        let secret = CapSecret::from([7; 64]);
+        // The secret is desensitized away: only the assignee tells grants apart.
+        assert_eq!(
+            signing_grant_access(key.clone(), secret),
+            signing_grant_access(key.clone(), CapSecret::from([8; 64]))
+        );
+        assert_ne!(
+            signing_grant_access(key, secret),
+            signing_grant_access(other, secret)
+        );
+    }
+
+    #[test]
+    fn allowed_functions_default_to_everything() {
+        let cfg = HamConfig::new(30000, 30001, "app");
+        assert!(cfg.allows_call("ledger", "commit_batch"));
+        assert!(cfg.granted_functions().is_none());
+    }
+
+    #[test]
+    fn allowed_functions_scope_calls_and_grant() {
+        let cfg = HamConfig::new(30000, 30001, "app")
+            .with_allowed_function("ledger", "get_balance")
+            .with_allowed_function("pricing", "get_quote");
+        assert!(cfg.allows_call("ledger", "get_balance"));
+        assert!(cfg.allows_call("pricing", "get_quote"));
+        // Zome and fn must match as a pair.
+        assert!(!cfg.allows_call("ledger", "get_quote"));
+        assert!(!cfg.allows_call("ledger", "commit_batch"));
+
+        let Some(GrantedFunctions::Listed(granted)) = cfg.granted_functions() else {
+            panic!("expected a listed grant");
+        };
+        assert_eq!(granted.len(), 2);
+        assert!(granted.contains(&("ledger".into(), "get_balance".into())));
+        assert!(granted.contains(&("pricing".into(), "get_quote".into())));
+    }
+
+    #[test]
+    fn unbracket_accepts_both_ipv6_literal_forms() {
+        assert_eq!(unbracket("[::1]"), "::1");
+        assert_eq!(unbracket("::1"), "::1");
+        assert_eq!(unbracket("127.0.0.1"), "127.0.0.1");
+        assert_eq!(unbracket("conductor.internal"), "conductor.internal");
+        // Half a bracket pair isn't URL-style; leave it for resolution to
+        // reject.
+        assert_eq!(unbracket("[::1"), "[::1");
+    }
+
     #[test]
     fn strip_passphrase_drops_trailing_newlines_only() {
         assert_eq!(strip_passphrase(b"deadbeef\n".to_vec()), b"deadbeef");
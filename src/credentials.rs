@@ -0,0 +1,441 @@
+//! [`CredentialStore`] &mdash; client-signing credentials kept across
+//! connects, so a reconnecting daemon reuses its cap grant instead of
+//! committing a fresh one every time.
+
+use anyhow::{Context, Result};
+use holochain_client::{AgentPubKey, CellId, SigningCredentials};
+use holochain_types::prelude::CapSecret;
+use lair_keystore_api::dependencies::sodoken::{self, SizedLockedArray};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+/// Leading bytes of a credential file; bump the digit on a format change.
+const MAGIC: &[u8; 8] = b"HAMCRED1";
+
+/// Signing credentials authorized by earlier connects, keyed by cell.
+///
+/// [`Ham::connect`](crate::Ham::connect) signs a cell found here with the
+/// stored key instead of authorizing a new one, so no cap grant is
+/// committed. Cheap to clone: clones share one store, which is how every
+/// `cfg.clone()` handed to a reconnect loop sees the same credentials.
+///
+/// By default the store lives in memory only, covering reconnects within one
+/// process. [`CredentialStore::open_encrypted`] backs it with a
+/// passphrase-encrypted file so restarts reuse the grants too.
+///
+/// A stored credential outlives its grant if that grant is revoked out of
+/// band. The first call the conductor then refuses as unauthorized evicts
+/// the entry and, given admin access, authorizes a replacement before
+/// re-sending the call once.
+#[derive(Clone, Default)]
+pub struct CredentialStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    credentials: HashMap<CellId, Arc<SigningCredentials>>,
+    file: Option<CredentialFile>,
+}
+
+/// Where and how the store persists: the argon2id-derived secretbox key is
+/// derived once at open, so writes don't pay for the password hash again.
+struct CredentialFile {
+    path: PathBuf,
+    salt: [u8; sodoken::argon2::ARGON2_ID_SALTBYTES],
+    key: SizedLockedArray<{ sodoken::secretbox::XSALSA_KEYBYTES }>,
+}
+
+/// On-disk form of one [`SigningCredentials`]. The keypair bytes are wiped
+/// when it drops.
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    cell_id: CellId,
+    signing_agent_key: AgentPubKey,
+    keypair: Zeroizing<[u8; 32]>,
+    cap_secret: CapSecret,
+}
+
+impl std::fmt::Debug for CredentialStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never render keys or secrets — just what's there and where.
+        let inner = self.lock();
+        f.debug_struct("CredentialStore")
+            .field("cells", &inner.credentials.len())
+            .field("file", &inner.file.as_ref().map(|f| f.path.display()))
+            .finish()
+    }
+}
+
+impl CredentialStore {
+    /// An empty, memory-only store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store backed by the encrypted file at `path`, loading whatever it
+    /// already holds. A missing file starts empty and is created on the first
+    /// insert. `passphrase` keys the encryption (argon2id, then xsalsa20poly1305
+    /// secretbox); a wrong passphrase or a tampered file is an error.
+    ///
+    /// Runs argon2id synchronously, which takes a noticeable fraction of a
+    /// second — call it at startup, not on a hot path.
+    pub fn open_encrypted(path: impl Into<PathBuf>, passphrase: &[u8]) -> Result<Self> {
+        let path = path.into();
+        let existing = match std::fs::read(&path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("reading credential file {}", path.display()))
+            }
+        };
+
+        let mut salt = [0; sodoken::argon2::ARGON2_ID_SALTBYTES];
+        match &existing {
+            Some(bytes) => salt.copy_from_slice(header(bytes, &path)?.0),
+            None => sodoken::random::randombytes_buf(&mut salt)
+                .map_err(|e| anyhow::anyhow!("generating credential file salt: {e}"))?,
+        }
+        let mut file = CredentialFile {
+            key: derive_key(passphrase, &salt)?,
+            salt,
+            path,
+        };
+
+        let mut credentials = HashMap::new();
+        if let Some(bytes) = existing {
+            for stored in file.decrypt(&bytes)? {
+                credentials.insert(
+                    stored.cell_id,
+                    Arc::new(SigningCredentials {
+                        signing_agent_key: stored.signing_agent_key,
+                        keypair: (&*stored.keypair).into(),
+                        cap_secret: stored.cap_secret,
+                    }),
+                );
+            }
+        }
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner {
+                credentials,
+                file: Some(file),
+            })),
+        })
+    }
+
+    /// A copy of the credentials stored for `cell_id`, if any.
+    pub fn get(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        self.lock()
+            .credentials
+            .get(cell_id)
+            .map(|c| copy_credentials(c))
+    }
+
+    /// A copy of every stored entry, for seeding a signer.
+    pub(crate) fn entries(&self) -> Vec<(CellId, SigningCredentials)> {
+        self.lock()
+            .credentials
+            .iter()
+            .map(|(cell_id, c)| (cell_id.clone(), copy_credentials(c)))
+            .collect()
+    }
+
+    /// Store `credentials` for `cell_id`, replacing any earlier entry, and
+    /// rewrite the backing file if there is one.
+    pub fn insert(&self, cell_id: CellId, credentials: &SigningCredentials) -> Result<()> {
+        let mut inner = self.lock();
+        inner
+            .credentials
+            .insert(cell_id, Arc::new(copy_credentials(credentials)));
+        inner.persist()
+    }
+
+    /// The signing key stored for `cell_id`, without copying its keypair.
+    pub(crate) fn signing_key(&self, cell_id: &CellId) -> Option<AgentPubKey> {
+        self.lock()
+            .credentials
+            .get(cell_id)
+            .map(|c| c.signing_agent_key.clone())
+    }
+
+    /// Forget the credentials for `cell_id` — e.g. after revoking its grant —
+    /// and rewrite the backing file if there is one.
+    pub fn remove(&self, cell_id: &CellId) -> Result<()> {
+        let mut inner = self.lock();
+        if inner.credentials.remove(cell_id).is_none() {
+            return Ok(());
+        }
+        inner.persist()
+    }
+
+    /// Number of cells with stored credentials.
+    pub fn len(&self) -> usize {
+        self.lock().credentials.len()
+    }
+
+    /// Whether no cell has stored credentials.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("credential store lock poisoned")
+    }
+}
+
+impl Inner {
+    fn persist(&mut self) -> Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        let stored: Vec<Stored> = self
+            .credentials
+            .iter()
+            .map(|(cell_id, c)| Stored {
+                cell_id: cell_id.clone(),
+                signing_agent_key: c.signing_agent_key.clone(),
+                keypair: Zeroizing::new(c.keypair.to_bytes()),
+                cap_secret: c.cap_secret,
+            })
+            .collect();
+        file.write(&stored)
+    }
+}
+
+impl CredentialFile {
+    /// `MAGIC | salt | nonce | secretbox(msgpack(entries))`, written to a
+    /// sibling temp file (`<path>.tmp`, owner-only on unix) and renamed into place, so a
+    /// crash mid-write never leaves a truncated store.
+    fn write(&mut self, entries: &[Stored]) -> Result<()> {
+        let plain =
+            Zeroizing::new(rmp_serde::to_vec_named(entries).context("encoding credentials")?);
+        let mut nonce = [0; sodoken::secretbox::XSALSA_NONCEBYTES];
+        sodoken::random::randombytes_buf(&mut nonce)
+            .map_err(|e| anyhow::anyhow!("generating credential file nonce: {e}"))?;
+        let mut cipher = vec![0; plain.len() + sodoken::secretbox::XSALSA_MACBYTES];
+        sodoken::secretbox::xsalsa_easy(&mut cipher, &nonce, &plain, &self.key.lock())
+            .map_err(|e| anyhow::anyhow!("encrypting credentials: {e}"))?;
+
+        let mut out =
+            Vec::with_capacity(MAGIC.len() + self.salt.len() + nonce.len() + cipher.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&cipher);
+
+        let mut tmp = OsString::from(self.path.as_os_str());
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        write_private(&tmp, &out)
+            .with_context(|| format!("writing credential file {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replacing credential file {}", self.path.display()))
+    }
+
+    fn decrypt(&mut self, bytes: &[u8]) -> Result<Vec<Stored>> {
+        let (_, nonce, cipher) = header(bytes, &self.path)?;
+        anyhow::ensure!(
+            cipher.len() >= sodoken::secretbox::XSALSA_MACBYTES,
+            "credential file {} is truncated",
+            self.path.display()
+        );
+        let mut plain = Zeroizing::new(vec![0; cipher.len() - sodoken::secretbox::XSALSA_MACBYTES]);
+        sodoken::secretbox::xsalsa_open_easy(&mut plain, cipher, nonce, &self.key.lock()).map_err(
+            |_| {
+                anyhow::anyhow!(
+                    "Failed to decrypt credential file {}: wrong passphrase or corrupted file",
+                    self.path.display()
+                )
+            },
+        )?;
+        rmp_serde::from_slice(&plain).context("decoding credential file")
+    }
+}
+
+/// Split a credential file into salt, nonce and ciphertext.
+#[allow(clippy::type_complexity)]
+fn header<'a>(
+    bytes: &'a [u8],
+    path: &Path,
+) -> Result<(
+    &'a [u8],
+    &'a [u8; sodoken::secretbox::XSALSA_NONCEBYTES],
+    &'a [u8],
+)> {
+    let rest = bytes
+        .strip_prefix(MAGIC.as_slice())
+        .with_context(|| format!("{} is not a ham credential file", path.display()))?;
+    anyhow::ensure!(
+        rest.len() >= sodoken::argon2::ARGON2_ID_SALTBYTES + sodoken::secretbox::XSALSA_NONCEBYTES,
+        "credential file {} is truncated",
+        path.display()
+    );
+    let (salt, rest) = rest.split_at(sodoken::argon2::ARGON2_ID_SALTBYTES);
+    let (nonce, cipher) = rest.split_at(sodoken::secretbox::XSALSA_NONCEBYTES);
+    Ok((
+        salt,
+        nonce.try_into().expect("split at nonce length"),
+        cipher,
+    ))
+}
+
+fn derive_key(
+    passphrase: &[u8],
+    salt: &[u8; sodoken::argon2::ARGON2_ID_SALTBYTES],
+) -> Result<SizedLockedArray<{ sodoken::secretbox::XSALSA_KEYBYTES }>> {
+    let mut key =
+        SizedLockedArray::new().map_err(|e| anyhow::anyhow!("allocating credential key: {e}"))?;
+    sodoken::argon2::blocking_argon2id(
+        &mut *key.lock(),
+        passphrase,
+        salt,
+        sodoken::argon2::ARGON2_ID_OPSLIMIT_INTERACTIVE,
+        sodoken::argon2::ARGON2_ID_MEMLIMIT_INTERACTIVE,
+    )
+    .map_err(|e| anyhow::anyhow!("deriving credential file key: {e}"))?;
+    Ok(key)
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+/// `SigningCredentials` isn't `Clone`; copy it field by field.
+pub(crate) fn copy_credentials(c: &SigningCredentials) -> SigningCredentials {
+    SigningCredentials {
+        signing_agent_key: c.signing_agent_key.clone(),
+        keypair: c.keypair.clone(),
+        cap_secret: c.cap_secret,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_types::prelude::DnaHash;
+
+    fn cell(n: u8) -> CellId {
+        CellId::new(
+            DnaHash::from_raw_32(vec![n; 32]),
+            AgentPubKey::from_raw_32(vec![n; 32]),
+        )
+    }
+
+    fn credentials(n: u8) -> SigningCredentials {
+        SigningCredentials {
+            signing_agent_key: AgentPubKey::from_raw_32(vec![n.wrapping_add(1); 32]),
+            keypair: (&[n; 32]).into(),
+            cap_secret: [n; 64].into(),
+        }
+    }
+
+    /// A path under the system temp dir that no other test (or run) uses.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ham-{}-{name}.cred", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn assert_same(a: &SigningCredentials, b: &SigningCredentials) {
+        assert_eq!(a.signing_agent_key, b.signing_agent_key);
+        assert_eq!(a.keypair.to_bytes(), b.keypair.to_bytes());
+        assert_eq!(a.cap_secret, b.cap_secret);
+    }
+
+    #[test]
+    fn clones_share_one_store() {
+        let store = CredentialStore::new();
+        let clone = store.clone();
+        store.insert(cell(1), &credentials(1)).unwrap();
+        assert_same(&clone.get(&cell(1)).unwrap(), &credentials(1));
+        clone.remove(&cell(1)).unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn encrypted_file_survives_reopen() {
+        let path = temp_path("reopen");
+        let store = CredentialStore::open_encrypted(&path, b"correct horse").unwrap();
+        assert!(store.is_empty());
+        store.insert(cell(1), &credentials(1)).unwrap();
+        store.insert(cell(2), &credentials(2)).unwrap();
+
+        let reopened = CredentialStore::open_encrypted(&path, b"correct horse").unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert_same(&reopened.get(&cell(1)).unwrap(), &credentials(1));
+        assert_same(&reopened.get(&cell(2)).unwrap(), &credentials(2));
+
+        // The keypair never hits the disk in the clear.
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(32).any(|w| w == [1u8; 32]));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let path = temp_path("wrong-passphrase");
+        let store = CredentialStore::open_encrypted(&path, b"correct horse").unwrap();
+        store.insert(cell(1), &credentials(1)).unwrap();
+
+        let err = CredentialStore::open_encrypted(&path, b"battery staple").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("wrong passphrase"),
+            "got {err:#}"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn tampered_file_is_rejected() {
+        let path = temp_path("tampered");
+        let store = CredentialStore::open_encrypted(&path, b"correct horse").unwrap();
+        store.insert(cell(1), &credentials(1)).unwrap();
+
+        let mut raw = std::fs::read(&path).unwrap();
+        *raw.last_mut().unwrap() ^= 0xff;
+        std::fs::write(&path, raw).unwrap();
+        assert!(CredentialStore::open_encrypted(&path, b"correct horse").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn temp_file_keeps_the_store_extension() {
+        let path = temp_path("extension");
+        // What `with_extension("tmp")` would have clobbered.
+        let neighbour = path.with_extension("tmp");
+        std::fs::write(&neighbour, b"someone else's").unwrap();
+        let store = CredentialStore::open_encrypted(&path, b"correct horse").unwrap();
+        store.insert(cell(1), &credentials(1)).unwrap();
+        assert_eq!(std::fs::read(&neighbour).unwrap(), b"someone else's");
+        std::fs::remove_file(&neighbour).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn foreign_file_is_rejected() {
+        let path = temp_path("foreign");
+        std::fs::write(&path, b"not a credential file").unwrap();
+        let err = CredentialStore::open_encrypted(&path, b"correct horse").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("not a ham credential file"),
+            "got {err:#}"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
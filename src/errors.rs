@@ -1,60 +1,673 @@
-//! Error classification helpers shared across all consumers.
+//! Error classification helpers shared across all consumers, and the
+//! [`HamError`] every conductor failure surfaced by [`Ham`](crate::Ham)
+//! carries.
+
+use crate::classifier::{default_classifier, ham_error, RuleClass};
+use holochain_client::{CellId, ConductorApiError};
+use holochain_types::app::AppStatus;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A failed conductor interaction, classified once where it happened.
+///
+/// [`Ham`](crate::Ham) methods still return [`anyhow::Result`]; the
+/// `HamError` rides inside and is recovered with
+/// `err.downcast_ref::<HamError>()` (which sees through any `.context()`
+/// added on top). The upstream error is kept as [`source`], so
+/// `format!("{err:#}")` renders the full chain while `Display` shows only
+/// what `Ham` was doing.
+///
+/// The classifier functions below check for a `HamError` anywhere in the
+/// chain first and answer from its variant when that is structural (see
+/// [`Classifier`](crate::Classifier)); transport and other conductor
+/// variants, and errors that never passed through one, are matched on the
+/// rendered text.
+///
+/// [`source`]: std::error::Error::source
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HamError {
+    /// The socket is gone; rebuild it (see [`is_connection_error`]).
+    Connection {
+        /// What `Ham` was doing.
+        context: String,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+    /// A per-request timeout on a healthy socket (see [`is_request_timeout`]).
+    RequestTimeout {
+        /// What `Ham` was doing.
+        context: String,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+    /// The conductor is under source-chain pressure (see
+    /// [`is_source_chain_pressure`]).
+    SourceChainPressure {
+        /// What `Ham` was doing.
+        context: String,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+    /// The zome call reached the guest and the guest (or the ribosome on its
+    /// behalf) returned an error.
+    ZomeGuest {
+        /// What `Ham` was doing.
+        context: String,
+        /// The guest error, parsed out of the upstream one.
+        error: ZomeError,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+    /// The call couldn't be signed, or the conductor rejected its signature
+    /// or capability.
+    Auth {
+        /// What `Ham` was doing.
+        context: String,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+    /// The conductor answered, but has no app by this id installed.
+    NotInstalled {
+        /// The app that was looked up.
+        app_id: String,
+    },
+    /// The app is installed but not enabled (disabled, awaiting membrane
+    /// proofs, or unrecoverable), so its cells can't be called.
+    AppDisabled {
+        /// The app that was looked up.
+        app_id: String,
+        /// The status the conductor reported.
+        status: AppStatus,
+    },
+    /// The app has no provisioned cell to call.
+    NoProvisionedCells {
+        /// The app that was looked up.
+        app_id: String,
+    },
+    /// The call was refused locally, before reaching the conductor: the
+    /// function isn't in [`HamConfig::allowed_functions`](crate::HamConfig::allowed_functions).
+    FunctionNotAllowed {
+        /// The zome called.
+        zome_name: String,
+        /// The function called.
+        fn_name: String,
+    },
+    /// The conductor closed the app websocket with a close frame right after
+    /// a pre-issued token was presented (see
+    /// [`HamConfig::app_only`](crate::HamConfig::app_only)): the token is
+    /// unknown, expired or already used.
+    TokenRejected {
+        /// What `Ham` was doing.
+        context: String,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+    /// The zome call succeeded but its response didn't decode into the
+    /// requested type: the DNA and this caller disagree on the schema (see
+    /// [`is_schema_drift`]).
+    Deserialize {
+        /// What `Ham` was doing.
+        context: String,
+        /// The Rust type the response was decoded into.
+        type_name: &'static str,
+        /// The size of the msgpack response, in bytes.
+        response_len: usize,
+        /// The response as JSON, cut off after [`PAYLOAD_DUMP_LIMIT`] chars.
+        payload: String,
+        /// The decode error.
+        source: rmp_serde::decode::Error,
+    },
+    /// Any other conductor error.
+    Conductor {
+        /// What `Ham` was doing.
+        context: String,
+        /// The upstream error.
+        source: ConductorApiError,
+    },
+}
+
+impl HamError {
+    /// Classify `source`, a failure of `context`, into a variant. The
+    /// upstream variant decides which classes are possible — only transport
+    /// errors can be connection failures or timeouts, only conductor answers
+    /// can be pressure or guest errors — and within those the same text rules
+    /// as the free classifiers decide. So a guest error whose message happens
+    /// to mention a reset connection stays a guest error.
+    pub(crate) fn conductor(context: impl Into<String>, source: ConductorApiError) -> Self {
+        use holochain_conductor_api::ExternalApiWireError as Wire;
+
+        let context = context.into();
+        let msg = source.to_string();
+        let rules = default_classifier();
+        let matches = |class| rules.rule_for(class, &msg).is_some();
+        match &source {
+            ConductorApiError::WebsocketError(_) | ConductorApiError::IoError(_) => {
+                if matches(RuleClass::Connection) {
+                    Self::Connection { context, source }
+                } else if matches(RuleClass::RequestTimeout) {
+                    Self::RequestTimeout { context, source }
+                } else {
+                    Self::Conductor { context, source }
+                }
+            }
+            ConductorApiError::ExternalApiWireError(
+                Wire::ZomeCallAuthenticationFailed(_) | Wire::ZomeCallUnauthorized(_),
+            )
+            | ConductorApiError::SignZomeCallError(_)
+            | ConductorApiError::FreshNonceError(_) => Self::Auth { context, source },
+            ConductorApiError::ExternalApiWireError(_)
+                if matches(RuleClass::SourceChainPressure) =>
+            {
+                Self::SourceChainPressure { context, source }
+            }
+            ConductorApiError::ExternalApiWireError(Wire::RibosomeError(text)) => Self::ZomeGuest {
+                context,
+                error: ZomeError::parse(text),
+                source,
+            },
+            _ => Self::Conductor { context, source },
+        }
+    }
+
+    /// A zome response of `bytes` that didn't decode into `R`, with what
+    /// it actually held.
+    pub(crate) fn deserialize<R>(
+        context: impl Into<String>,
+        bytes: &[u8],
+        source: rmp_serde::decode::Error,
+    ) -> Self {
+        Self::Deserialize {
+            context: context.into(),
+            type_name: std::any::type_name::<R>(),
+            response_len: bytes.len(),
+            payload: msgpack_dump(bytes, PAYLOAD_DUMP_LIMIT),
+            source,
+        }
+    }
+
+    /// Record which function a failed zome call was addressed to, on the
+    /// variants that carry it.
+    pub(crate) fn for_call(mut self, target: CallTarget) -> Self {
+        if let Self::ZomeGuest { error, .. } = &mut self {
+            error.target = Some(target);
+        }
+        self
+    }
+
+    /// [`HamError::conductor`] for opening the app websocket of `app_id` with
+    /// a pre-issued token. The conductor rejects a token by closing the socket
+    /// with a close frame, so a close frame is [`HamError::TokenRejected`].
+    /// Anything else — a refused connect, a failed handshake, or the client's
+    /// own "connection closed" and "responder dropped" reports, which a
+    /// dropped link produces just the same — stays a connection failure.
+    pub(crate) fn app_token_connect(
+        app_id: &str,
+        context: impl Into<String>,
+        source: ConductorApiError,
+    ) -> Self {
+        use holochain_websocket::WebsocketError;
+
+        match &source {
+            ConductorApiError::AppNotFound => Self::NotInstalled {
+                app_id: app_id.to_string(),
+            },
+            ConductorApiError::WebsocketError(WebsocketError::Close(reason))
+                if is_close_frame(reason) =>
+            {
+                Self::TokenRejected {
+                    context: "App auth token rejected".to_string(),
+                    source,
+                }
+            }
+            _ => Self::conductor(context, source),
+        }
+    }
+
+    /// The rule class this variant stands for, if any.
+    pub(crate) fn rule_class(&self) -> Option<RuleClass> {
+        use holochain_conductor_api::ExternalApiWireError as Wire;
+
+        match self {
+            Self::Connection { .. } => Some(RuleClass::Connection),
+            Self::RequestTimeout { .. } => Some(RuleClass::RequestTimeout),
+            Self::SourceChainPressure { .. } => Some(RuleClass::SourceChainPressure),
+            Self::NotInstalled { .. }
+            | Self::AppDisabled { .. }
+            | Self::NoProvisionedCells { .. }
+            | Self::FunctionNotAllowed { .. }
+            | Self::TokenRejected { .. } => Some(RuleClass::Permanent),
+            Self::Deserialize { .. } => Some(RuleClass::SchemaDrift),
+            // A signing failure may be lair briefly unavailable; only the
+            // conductor refusing the grant itself is permanent.
+            Self::Auth {
+                source:
+                    ConductorApiError::ExternalApiWireError(
+                        Wire::ZomeCallAuthenticationFailed(_) | Wire::ZomeCallUnauthorized(_),
+                    ),
+                ..
+            } => Some(RuleClass::Permanent),
+            _ => None,
+        }
+    }
+
+    /// Whether the conductor refused the call's capability — the grant it was
+    /// signed under is gone or doesn't cover the function.
+    pub(crate) fn is_unauthorized(&self) -> bool {
+        use holochain_conductor_api::ExternalApiWireError as Wire;
+
+        matches!(
+            self,
+            Self::Auth {
+                source: ConductorApiError::ExternalApiWireError(Wire::ZomeCallUnauthorized(_)),
+                ..
+            }
+        )
+    }
+
+    /// Whether the variant alone decides the class: it was built from a
+    /// structural signal (a status, a typed upstream variant, a guest or
+    /// decode failure), not from text rules, so no classifier's rules
+    /// override it. Transport and other conductor variants are not.
+    pub(crate) fn is_structural(&self) -> bool {
+        match self {
+            Self::Connection { .. }
+            | Self::RequestTimeout { .. }
+            | Self::SourceChainPressure { .. }
+            | Self::Conductor { .. } => false,
+            // A signing failure is only text; a refused grant is typed.
+            Self::Auth { .. } => self.rule_class().is_some(),
+            Self::ZomeGuest { .. }
+            | Self::NotInstalled { .. }
+            | Self::AppDisabled { .. }
+            | Self::NoProvisionedCells { .. }
+            | Self::FunctionNotAllowed { .. }
+            | Self::TokenRejected { .. }
+            | Self::Deserialize { .. } => true,
+        }
+    }
+
+    /// `HamError::<Variant>`: the [`ErrorClass::rule`] of a typed error no
+    /// text rule names.
+    pub(crate) fn rule(&self) -> &'static str {
+        match self {
+            Self::Connection { .. } => "HamError::Connection",
+            Self::RequestTimeout { .. } => "HamError::RequestTimeout",
+            Self::SourceChainPressure { .. } => "HamError::SourceChainPressure",
+            Self::ZomeGuest { .. } => "HamError::ZomeGuest",
+            Self::Auth { .. } => "HamError::Auth",
+            Self::NotInstalled { .. } => "HamError::NotInstalled",
+            Self::AppDisabled { .. } => "HamError::AppDisabled",
+            Self::NoProvisionedCells { .. } => "HamError::NoProvisionedCells",
+            Self::FunctionNotAllowed { .. } => "HamError::FunctionNotAllowed",
+            Self::TokenRejected { .. } => "HamError::TokenRejected",
+            Self::Deserialize { .. } => "HamError::Deserialize",
+            Self::Conductor { .. } => "HamError::Conductor",
+        }
+    }
+
+    /// The guest's error, if the zome call got as far as the guest.
+    pub fn zome_error(&self) -> Option<&ZomeError> {
+        match self {
+            Self::ZomeGuest { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The upstream conductor error, if this failure came from one.
+    pub fn conductor_error(&self) -> Option<&ConductorApiError> {
+        match self {
+            Self::Connection { source, .. }
+            | Self::RequestTimeout { source, .. }
+            | Self::SourceChainPressure { source, .. }
+            | Self::ZomeGuest { source, .. }
+            | Self::Auth { source, .. }
+            | Self::TokenRejected { source, .. }
+            | Self::Conductor { source, .. } => Some(source),
+            Self::NotInstalled { .. }
+            | Self::AppDisabled { .. }
+            | Self::NoProvisionedCells { .. }
+            | Self::FunctionNotAllowed { .. }
+            | Self::Deserialize { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for HamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotInstalled { app_id, .. } => write!(f, "App `{app_id}` not installed"),
+            Self::AppDisabled { app_id, status } => {
+                write!(f, "App `{app_id}` is not enabled: {status:?}")
+            }
+            Self::Deserialize {
+                context,
+                type_name,
+                response_len,
+                payload,
+                ..
+            } => write!(
+                f,
+                "{context} as `{type_name}` ({response_len} bytes): {payload}"
+            ),
+            Self::NoProvisionedCells { app_id } => write!(
+                f,
+                "Invalid cell type: expected Provisioned (app `{app_id}` has no provisioned cell)"
+            ),
+            Self::FunctionNotAllowed { zome_name, fn_name } => write!(
+                f,
+                "Refusing to call {zome_name}/{fn_name}: not in HamConfig::allowed_functions"
+            ),
+            Self::Connection { context, .. }
+            | Self::RequestTimeout { context, .. }
+            | Self::SourceChainPressure { context, .. }
+            | Self::ZomeGuest { context, .. }
+            | Self::Auth { context, .. }
+            | Self::TokenRejected { context, .. }
+            | Self::Conductor { context, .. } => f.write_str(context),
+        }
+    }
+}
+
+impl std::error::Error for HamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Deserialize { source, .. } => Some(source),
+            _ => self
+                .conductor_error()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+/// Whether `reason`, from a `WebsocketError::Close`, is a close frame the
+/// peer sent — `holochain_websocket` renders one with `{:?}` — rather than
+/// one of the client's own sentinels for a socket that is already gone
+/// (`ConnectionClosed`, `No connection`).
+fn is_close_frame(reason: &str) -> bool {
+    reason == "None" || reason.starts_with("Some(CloseFrame")
+}
+
+/// How much of an undecodable response [`HamError::Deserialize`] keeps, in
+/// chars of JSON.
+pub const PAYLOAD_DUMP_LIMIT: usize = 2048;
+
+/// `bytes` as JSON for a human, at most `limit` chars (plus an ellipsis).
+/// Binary fields, which hashes and keys are, show as `0x…` hex; bytes that
+/// aren't msgpack at all show as hex too.
+fn msgpack_dump(bytes: &[u8], limit: usize) -> String {
+    let text = match rmpv::decode::read_value(&mut &bytes[..]) {
+        Ok(value) => msgpack_json(value).to_string(),
+        Err(_) => format!("<not msgpack> {}", hex(bytes)),
+    };
+    match text.char_indices().nth(limit) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+fn msgpack_json(value: rmpv::Value) -> serde_json::Value {
+    use rmpv::Value as Mp;
+    use serde_json::Value as Json;
+
+    match value {
+        Mp::Nil => Json::Null,
+        Mp::Boolean(b) => Json::Bool(b),
+        Mp::Integer(n) => n
+            .as_i64()
+            .map(Json::from)
+            .or_else(|| n.as_u64().map(Json::from))
+            .unwrap_or(Json::Null),
+        Mp::F32(n) => Json::from(f64::from(n)),
+        Mp::F64(n) => Json::from(n),
+        Mp::String(s) => match s.into_str() {
+            Some(s) => Json::String(s),
+            None => Json::String("<invalid utf-8>".to_string()),
+        },
+        Mp::Binary(b) => Json::String(hex(&b)),
+        Mp::Array(items) => Json::Array(items.into_iter().map(msgpack_json).collect()),
+        Mp::Map(entries) => Json::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Mp::String(s) if s.is_str() => s.into_str().unwrap_or_default(),
+                        k => msgpack_json(k).to_string(),
+                    };
+                    (key, msgpack_json(v))
+                })
+                .collect(),
+        ),
+        Mp::Ext(kind, data) => serde_json::json!({ "ext": kind, "data": hex(&data) }),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes.iter().fold(String::from("0x"), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+/// The [`ZomeError`] in `err`'s chain, if the failure came from a zome's own
+/// code — so callers can branch on an application-level rejection (a failed
+/// validation, a guest `Err`) without matching on its text.
+pub fn zome_error(err: &anyhow::Error) -> Option<&ZomeError> {
+    ham_error(err)?.zome_error()
+}
+
+/// What a guest error was, as the ribosome reported it: the
+/// `WasmErrorInner` variant it wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ZomeErrorKind {
+    /// The zome returned an error of its own (`wasm_error!(Guest(..))`, or an
+    /// `Err(String)` converted into one) — typically a failed validation or
+    /// precondition.
+    Guest,
+    /// A host function the zome called failed.
+    Host,
+    /// Something failed to serialize inside the ribosome.
+    Serialize,
+    /// The zome's input (or a host function's output) didn't deserialize —
+    /// usually a payload of the wrong shape.
+    Deserialize,
+    /// The host couldn't call the function at all — e.g. it doesn't exist.
+    CallError,
+    /// A ribosome error with no recognizable `WasmError` in it.
+    Other,
+}
+
+/// The function a zome call was addressed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallTarget {
+    /// The role called, `None` for [`Ham::call_zome_on_cell`](crate::Ham::call_zome_on_cell).
+    pub role_name: Option<String>,
+    /// The cell called, when `Ham` knows it.
+    pub cell_id: Option<CellId>,
+    /// The zome called.
+    pub zome_name: String,
+    /// The function called.
+    pub fn_name: String,
+}
+
+impl std::fmt::Display for CallTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(role_name) = &self.role_name {
+            write!(f, "{role_name}/")?;
+        }
+        write!(f, "{}/{}", self.zome_name, self.fn_name)
+    }
+}
+
+/// Which zome call failed: the context on every error a [`Ham`](crate::Ham)
+/// zome call returns, outermost in the chain, so logs of a multi-step cycle
+/// say which step broke. Recover it with `err.downcast_ref::<ZomeCall>()`; the
+/// [`HamError`] underneath is still found by `downcast_ref::<HamError>()`.
+///
+/// `request_id` is also on the call's `ham.call_zome` tracing span and its
+/// `ham.call_zome.done` event, to join an error to its logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZomeCall {
+    /// Unique per process, increasing in call order. Each attempt of a
+    /// retried call gets its own.
+    pub request_id: u64,
+    /// The function called.
+    pub target: CallTarget,
+    /// How long the call ran before failing.
+    pub elapsed: Duration,
+}
+
+impl std::fmt::Display for ZomeCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Zome call #{} to {} failed after {:?}",
+            self.request_id, self.target, self.elapsed
+        )
+    }
+}
+
+/// A zome call's failure inside the guest, recovered from the ribosome's
+/// rendering of the `WasmError` (see [`zome_error`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZomeError {
+    /// What kind of failure it was.
+    pub kind: ZomeErrorKind,
+    /// The guest's message, unquoted — for [`ZomeErrorKind::Guest`] exactly
+    /// the string the zome returned. The whole ribosome error for
+    /// [`ZomeErrorKind::Other`].
+    pub message: String,
+    /// The function called. `None` outside a zome call (e.g. a clone-cell
+    /// operation that ran a callback).
+    pub target: Option<CallTarget>,
+}
+
+impl std::fmt::Display for ZomeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} error", self.kind)?;
+        if let Some(target) = &self.target {
+            write!(f, " from {target}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl ZomeError {
+    /// Parse the text of an `ExternalApiWireError::RibosomeError`. A
+    /// `WasmError` renders as `module::path:line: <inner:?>`, and the
+    /// ribosome wraps that in prefixes of its own, so this finds the first
+    /// `Guest(`/`Host(`/… token and takes its balanced argument.
+    fn parse(text: &str) -> Self {
+        const KINDS: [(&str, ZomeErrorKind); 5] = [
+            ("Guest(", ZomeErrorKind::Guest),
+            ("Host(", ZomeErrorKind::Host),
+            ("Serialize(", ZomeErrorKind::Serialize),
+            ("Deserialize(", ZomeErrorKind::Deserialize),
+            ("CallError(", ZomeErrorKind::CallError),
+        ];
+        // The earliest token wins: `Serialize(Deserialize(..))` is a
+        // serialize error whose cause happens to be a deserialize one.
+        let found = KINDS
+            .iter()
+            .filter_map(|&(token, kind)| {
+                let start = text.match_indices(token).map(|(i, _)| i).find(|&i| {
+                    // Whole variant names only: not `ModuleDeserialize(`.
+                    !text[..i]
+                        .chars()
+                        .next_back()
+                        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+                })?;
+                Some((start, token.len(), kind))
+            })
+            .min_by_key(|&(start, ..)| start);
+        let parsed = found.and_then(|(start, len, kind)| {
+            let arg = balanced_arg(&text[start + len..])?;
+            Some((kind, unquote(arg).unwrap_or_else(|| arg.to_string())))
+        });
+        let (kind, message) = parsed.unwrap_or_else(|| (ZomeErrorKind::Other, text.to_string()));
+        Self {
+            kind,
+            message,
+            target: None,
+        }
+    }
+}
+
+/// The text up to the `)` closing an argument list that has just been
+/// opened, skipping parentheses inside string literals.
+fn balanced_arg(text: &str) -> Option<&str> {
+    let (mut depth, mut in_string, mut escaped) = (1u32, false, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[..i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Undo `{:?}` on a string: `"a \"b\""` → `a "b"`. `None` if `text` isn't
+/// a single quoted literal.
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return None;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            'u' => {
+                let hex: String = chars
+                    .by_ref()
+                    .skip_while(|&c| c == '{')
+                    .take_while(|&c| c != '}')
+                    .collect();
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            other => other,
+        });
+    }
+    Some(out)
+}
 
 /// Classifies whether an `anyhow::Error` looks like a websocket / transport
 /// failure that warrants rebuilding the `Ham` connection. Matches against the
 /// rendered error chain so it handles both direct `holochain_client` failures
 /// and wrapped context messages.
 ///
-/// This is string-based because `holochain_client 0.9.x` surfaces websocket
+/// An error carrying a [`HamError`] is answered from its variant. Otherwise
+/// this is string-based, because `holochain_client 0.9.x` surfaces websocket
 /// failures as opaque strings inside `ConductorApiError::WebsocketError(_)`
-/// and similar variants. The `upstream_error_text` tests build real upstream
-/// error values, so a bump that reworded one of the paths they cover fails the
-/// suite — see that module for which paths those are, and which are still only
-/// covered by the hand-written strings below.
+/// and similar variants — the same rules decide the variant when a
+/// `HamError` is built. The rules are the [`default_classifier`]'s: the
+/// built-in needles (see [`Classifier`](crate::Classifier)) plus any a
+/// consumer added. The `upstream_error_text` tests build real upstream error
+/// values, so a bump that reworded one of the paths they cover fails the
+/// suite — see that module for which paths those are, and which are still
+/// only covered by hand-written strings.
 pub fn is_connection_error(err: &anyhow::Error) -> bool {
-    // Lowercased before matching, so the needles below are all lowercase: this
-    // classifier alone aggregates text from layers that capitalize differently
-    // — tungstenite's `"Connection reset without closing handshake"` vs. an io
-    // error's `"connection reset by peer"`. Its two siblings below stay
-    // case-sensitive on purpose: each matches one exact upstream `Display`
-    // composition, where the case is part of what pins that composition.
-    let msg = format!("{err:#}").to_lowercase();
-    // IMPORTANT: do NOT use a bare `"websocket error"` needle here. That
-    // phrase is emitted for both genuine transport failures AND for
-    // per-request timeouts (`"Websocket error: Timeout"`), and the latter
-    // must NOT trigger a reconnect — the socket is still healthy. See
-    // [`is_request_timeout`] for that case.
-    const NEEDLES: &[&str] = &[
-        "websocket closed",
-        "no connection",
-        "broken pipe",
-        "connection reset",
-        "io error",
-        // Post-handshake tungstenite owns the stream, so its own text arrives
-        // as a `WebsocketError::Websocket(_)` passthrough sharing no substring
-        // with the needles above. A send walks these as the socket goes down:
-        // `SendAfterClosing` once either side's close frame has landed,
-        // `AlreadyClosed` once it is terminated, and `ConnectionClosed` when
-        // the send syscall itself resets while the state says we can no longer
-        // read.
-        "sending after closing",
-        "trying to work with closed connection",
-        "connection closed",
-        // Defensive safety net: the reachable 0.7 renderings of these are
-        // already matched by broader needles above — a `Close("ConnectionClosed")`
-        // payload by `"websocket closed"`, the reset text by `"connection reset"`.
-        // Kept in case a future rendering surfaces the bare variant name instead.
-        "connectionclosed",
-        "resetwithoutclosinghandshake",
-        // `holochain_websocket` resolves an in-flight request with
-        // `WebsocketError::Other("ResponderDropped")` when the request's oneshot
-        // responder is dropped before it replies — the connection core tore down
-        // under an outstanding call, so the request is dead and the socket's
-        // state is unknown. Rebuild rather than retry on a socket we can't trust.
-        // (Matched lowercased; the rendered text is the CamelCase variant name.)
-        "responderdropped",
-    ];
-    NEEDLES.iter().any(|n| msg.contains(n))
+    default_classifier().is_connection_error(err)
 }
 
 /// Classifies whether an `anyhow::Error` is a *per-request* timeout from the
@@ -77,8 +690,7 @@ pub fn is_connection_error(err: &anyhow::Error) -> bool {
 /// cooldown on a dead connection. Self-correcting — the retry then gets
 /// `Websocket closed: No connection` and reconnects.
 pub fn is_request_timeout(err: &anyhow::Error) -> bool {
-    let msg = format!("{err:#}");
-    msg.contains("Websocket error: Timeout")
+    default_classifier().is_request_timeout(err)
 }
 
 /// Classifies whether an `anyhow::Error` represents server-side *source-chain
@@ -94,13 +706,93 @@ pub fn is_request_timeout(err: &anyhow::Error) -> bool {
 /// two classes overlap zero in practice and deserve different handling
 /// (reconnect vs. cooldown).
 pub fn is_source_chain_pressure(err: &anyhow::Error) -> bool {
-    let msg = format!("{err:#}");
-    msg.contains("deadline has elapsed") || msg.contains("Source chain error")
+    default_classifier().is_source_chain_pressure(err)
+}
+
+/// Classifies whether an `anyhow::Error` is a *permanent* failure: the
+/// conductor is reachable but the setup is wrong — the app isn't installed,
+/// isn't enabled or has no provisioned cell, the app auth token was
+/// rejected, or the conductor refused the zome call's capability. Retrying
+/// or reconnecting can't fix any of these until someone changes the
+/// conductor or the config, so [`connect_with_backoff`] gives up on them at
+/// once and alerting should fire.
+///
+/// Answered from the [`HamError`] variant when there is one, otherwise from
+/// the rendered text like the other classifiers.
+///
+/// [`connect_with_backoff`]: crate::connect_with_backoff
+pub fn is_permanent_error(err: &anyhow::Error) -> bool {
+    default_classifier().is_permanent_error(err)
+}
+
+/// Classifies whether an `anyhow::Error` is a zome response that didn't
+/// decode into the type asked for: the DNA's schema and this caller's types
+/// have drifted apart. Not a transport problem and not retryable — the same
+/// call returns the same bytes — so it is [`Action::FailFast`] too, but kept
+/// apart from [`is_permanent_error`]: the conductor is configured fine and
+/// reconnecting is harmless. [`HamError::Deserialize`] says what arrived.
+pub fn is_schema_drift(err: &anyhow::Error) -> bool {
+    default_classifier().is_schema_drift(err)
+}
+
+/// What a caller should do about a failed call, as decided by [`classify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// The socket is dead: rebuild it ([`is_connection_error`]).
+    Reconnect,
+    /// The socket is fine and the call was merely slow: retry it on the same
+    /// socket after a short pause ([`is_request_timeout`]).
+    RetrySameSocket,
+    /// The conductor is struggling: back off before retrying
+    /// ([`is_source_chain_pressure`]).
+    Cooldown,
+    /// Misconfiguration or schema drift: stop retrying and alert
+    /// ([`is_permanent_error`], [`is_schema_drift`]).
+    FailFast,
+    /// Nothing a retry would fix, or nothing recognized: surface the error.
+    Fail,
+}
+
+/// The outcome of [`classify`]: what to do, and which rule said so.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ErrorClass {
+    /// The recommended reaction.
+    pub action: Action,
+    /// The rule that matched, for logs and metrics: the [`Rule`](crate::classifier::Rule)
+    /// name for the transport classes (a built-in rule is named after its
+    /// needle), `HamError::<Variant>` for a typed error outside them, or
+    /// `"unclassified"`.
+    pub rule: Arc<str>,
+}
+
+/// Classify `err` once, in the order every caller needs: permanent failures
+/// first (a rejected token closes the socket, and must not read as a
+/// connection failure), then connection failures (a dead socket never gets a
+/// same-socket retry), then per-request timeouts, then source-chain
+/// pressure, otherwise [`Action::Fail`].
+///
+/// Agrees with the four `is_*` classifiers — for an error with a
+/// [`HamError`] in its chain the variant decides the action, exactly as it
+/// does for them — but settles the overlaps between them in one place. Uses
+/// the [`default_classifier`]; see
+/// [`Classifier::classify`](crate::Classifier::classify).
+pub fn classify(err: &anyhow::Error) -> ErrorClass {
+    default_classifier().classify(err)
+}
+
+impl ErrorClass {
+    pub(crate) fn new(action: Action, rule: Arc<str>) -> Self {
+        Self { action, rule }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{is_connection_error, is_request_timeout, is_source_chain_pressure};
+    use super::{
+        classify, is_connection_error, is_permanent_error, is_request_timeout, is_schema_drift,
+        is_source_chain_pressure, msgpack_dump, unquote, Action, CallTarget, ErrorClass, HamError,
+        ZomeCall, ZomeError, ZomeErrorKind,
+    };
     use anyhow::anyhow;
 
     fn wrap(base: &'static str) -> anyhow::Error {
@@ -247,6 +939,186 @@ mod tests {
         assert!(!is_source_chain_pressure(&e));
     }
 
+    #[test]
+    fn classify_names_the_matched_rule() {
+        let c = classify(&wrap("Websocket error: Websocket closed: No connection"));
+        assert_eq!(
+            c,
+            ErrorClass::new(Action::Reconnect, "websocket closed".into())
+        );
+        let c = classify(&wrap("Websocket error: Timeout"));
+        assert_eq!(
+            c,
+            ErrorClass::new(Action::RetrySameSocket, "Websocket error: Timeout".into())
+        );
+        let c = classify(&wrap("Source chain error: deadline has elapsed"));
+        assert_eq!(
+            c,
+            ErrorClass::new(Action::Cooldown, "deadline has elapsed".into())
+        );
+        let c = classify(&wrap("guest error: validation failed"));
+        assert_eq!(c, ErrorClass::new(Action::Fail, "unclassified".into()));
+    }
+
+    #[test]
+    fn classify_puts_connection_failures_first() {
+        // A send-phase timeout after the core was torn down can render with
+        // both texts; the dead socket wins.
+        let e = wrap("Websocket error: Timeout; Websocket closed: No connection");
+        assert!(is_request_timeout(&e));
+        assert_eq!(classify(&e).action, Action::Reconnect);
+    }
+
+    #[test]
+    fn classifies_misconfiguration_as_permanent() {
+        for text in [
+            "App `ledger` not installed",
+            "Invalid cell type: expected Provisioned",
+            "App `ledger` is not enabled: Disabled(User)",
+        ] {
+            let e = wrap(text);
+            assert!(is_permanent_error(&e), "{text}");
+            assert_eq!(classify(&e).action, Action::FailFast, "{text}");
+        }
+        assert!(!is_permanent_error(&wrap("guest error: validation failed")));
+    }
+
+    #[test]
+    fn permanent_failure_wins_over_the_closed_socket_it_caused() {
+        let e = wrap("App auth token rejected: Websocket error: Websocket closed: No connection");
+        assert_eq!(
+            classify(&e),
+            ErrorClass::new(Action::FailFast, "App auth token rejected".into())
+        );
+    }
+
+    #[test]
+    fn typed_misconfiguration_is_permanent() {
+        let e = anyhow::Error::from(HamError::NotInstalled {
+            app_id: "ledger".to_string(),
+        })
+        .context("Failed to connect");
+        assert!(is_permanent_error(&e));
+        assert!(!is_connection_error(&e));
+        assert_eq!(
+            classify(&e),
+            ErrorClass::new(Action::FailFast, "` not installed".into())
+        );
+    }
+
+    #[test]
+    fn refused_function_is_permanent() {
+        let e = anyhow::Error::from(HamError::FunctionNotAllowed {
+            zome_name: "transactions".to_string(),
+            fn_name: "delete_all".to_string(),
+        });
+        assert_eq!(
+            e.to_string(),
+            "Refusing to call transactions/delete_all: not in HamConfig::allowed_functions"
+        );
+        assert!(is_permanent_error(&e));
+        assert_eq!(
+            classify(&e),
+            ErrorClass::new(Action::FailFast, "HamError::FunctionNotAllowed".into())
+        );
+    }
+
+    #[test]
+    fn unquote_reverses_debug_formatting() {
+        for original in [
+            "plain",
+            "with \"quotes\" and \\",
+            "tab\tnew\nline",
+            "é ✓ \u{7}",
+        ] {
+            assert_eq!(unquote(&format!("{original:?}")).as_deref(), Some(original));
+        }
+        assert_eq!(unquote("Deserialize(\"x\")"), None);
+    }
+
+    #[test]
+    fn ribosome_text_without_a_wasm_error_is_other() {
+        let e = ZomeError::parse("Attempted to call a zome function that doesn't exist");
+        assert_eq!(e.kind, ZomeErrorKind::Other);
+        assert_eq!(
+            e.message,
+            "Attempted to call a zome function that doesn't exist"
+        );
+    }
+
+    #[test]
+    fn call_context_sits_on_top_of_the_classified_error() {
+        let call = ZomeCall {
+            request_id: 7,
+            target: CallTarget {
+                role_name: Some("ledger".to_string()),
+                cell_id: None,
+                zome_name: "transactions".to_string(),
+                fn_name: "create_spend".to_string(),
+            },
+            elapsed: std::time::Duration::from_millis(1500),
+        };
+        let e = wrap("Websocket error: Timeout").context(call.clone());
+        assert_eq!(
+            e.to_string(),
+            "Zome call #7 to ledger/transactions/create_spend failed after 1.5s"
+        );
+        assert_eq!(e.downcast_ref::<ZomeCall>(), Some(&call));
+        assert_eq!(classify(&e).action, Action::RetrySameSocket);
+    }
+
+    #[test]
+    fn undecodable_response_is_schema_drift_with_a_dump() {
+        #[derive(serde::Serialize)]
+        struct Spend {
+            amount: i64,
+            #[serde(with = "serde_bytes_compat")]
+            to: Vec<u8>,
+            memo: Option<String>,
+        }
+        // Serialize `to` as msgpack bin, the way holochain hashes are.
+        mod serde_bytes_compat {
+            pub fn serialize<S: serde::Serializer>(b: &[u8], s: S) -> Result<S::Ok, S::Error> {
+                s.serialize_bytes(b)
+            }
+        }
+        let bytes = rmp_serde::to_vec_named(&Spend {
+            amount: -3,
+            to: vec![0x84, 0x20, 0x24],
+            memo: None,
+        })
+        .unwrap();
+        let source = rmp_serde::from_slice::<u32>(&bytes).unwrap_err();
+        let e: anyhow::Error =
+            HamError::deserialize::<u32>("Failed to deserialize response", &bytes, source).into();
+        let shown = e.to_string();
+        let dump = shown
+            .strip_prefix(&format!(
+                "Failed to deserialize response as `u32` ({} bytes): ",
+                bytes.len()
+            ))
+            .expect("type and size shown");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(dump).unwrap(),
+            serde_json::json!({ "amount": -3, "to": "0x842024", "memo": null })
+        );
+        assert!(is_schema_drift(&e));
+        assert!(is_schema_drift(&anyhow!("{e:#}")));
+        assert!(!is_permanent_error(&e) && !is_connection_error(&e));
+        assert_eq!(
+            classify(&e),
+            ErrorClass::new(Action::FailFast, "Failed to deserialize response".into())
+        );
+    }
+
+    #[test]
+    fn payload_dump_is_bounded() {
+        let bytes = rmp_serde::to_vec(&"é".repeat(100)).unwrap();
+        assert_eq!(msgpack_dump(&bytes, 5), "\"éééé…");
+        // Truncated: an array of two holding one.
+        assert_eq!(msgpack_dump(&[0x92, 0x01], 100), "<not msgpack> 0x9201");
+    }
+
     /// The tests above feed hand-written strings, so they pin only the
     /// classifier. These build the *real* upstream error values and assert the
     /// exact text they render, so a `holochain_client` / `holochain_websocket`
@@ -270,31 +1142,292 @@ mod tests {
     /// (`ConductorApiError` renders it with `{0:?}`), which can shift with no
     /// `#[error(...)]` edit to review and no semver signal — the exact-string
     /// assertion in `source_chain_pressure_wire_error_*` is the only guard.
+    ///
+    /// These run against [`HolochainLine::NEWEST`](crate::HolochainLine), so
+    /// they are gated on the line the upstream crates actually are.
+    #[cfg(feature = "hc-0_7")]
     mod upstream_error_text {
-        use super::{is_connection_error, is_request_timeout, is_source_chain_pressure};
+        use super::{
+            classify, is_connection_error, is_permanent_error, is_request_timeout,
+            is_source_chain_pressure, Action, CallTarget, ErrorClass, HamError, ZomeErrorKind,
+        };
+        use crate::errors::zome_error;
+        use crate::test_support;
         use holochain_client::ConductorApiError;
         use holochain_conductor_api::ExternalApiWireError;
+        use holochain_types::prelude::SerializedBytesError;
+        use holochain_wasmer_common::WasmErrorInner;
         use holochain_websocket::WebsocketError;
+        use tungstenite::protocol::frame::coding::CloseCode;
+        use tungstenite::protocol::CloseFrame;
 
         /// Assert what upstream renders, then hand back the error in the shape
-        /// production builds it: every `Ham` method wraps with
-        /// `anyhow!("…: {}", e)` (`client.rs`), so Display is all the
-        /// classifiers ever see.
+        /// production builds it: a [`HamError`] classified from that text, in
+        /// an `anyhow::Error`.
         #[track_caller]
         fn rendering(e: ConductorApiError, expected: &str) -> anyhow::Error {
             assert_eq!(e.to_string(), expected, "upstream error text changed");
-            anyhow::anyhow!("Failed to call zome: {}", e)
+            HamError::conductor("Failed to call zome", e).into()
+        }
+
+        /// The same upstream error flattened to a string, as before
+        /// `HamError` — what the text fallback still has to classify
+        /// identically.
+        fn flattened(e: &anyhow::Error) -> anyhow::Error {
+            anyhow::anyhow!("{e:#}")
+        }
+
+        #[test]
+        fn typed_and_text_classification_agree() {
+            let cases = [
+                test_support::connection_closed(),
+                test_support::responder_dropped(),
+                test_support::source_chain_pressure(),
+                ConductorApiError::SignZomeCallError("no signature".to_string()),
+            ];
+            for e in cases {
+                let typed: anyhow::Error = HamError::conductor("Failed to call zome", e).into();
+                let text = flattened(&typed);
+                assert_eq!(
+                    is_connection_error(&typed),
+                    is_connection_error(&text),
+                    "{typed:#}"
+                );
+                assert_eq!(
+                    is_request_timeout(&typed),
+                    is_request_timeout(&text),
+                    "{typed:#}"
+                );
+                assert_eq!(
+                    is_source_chain_pressure(&typed),
+                    is_source_chain_pressure(&text),
+                    "{typed:#}"
+                );
+            }
+        }
+
+        #[test]
+        fn upstream_error_is_the_source_and_survives_context() {
+            let e = rendering(
+                test_support::connection_closed(),
+                "Websocket error: Websocket closed: No connection",
+            )
+            .context("Failed to refresh prices");
+            let ham = e
+                .downcast_ref::<HamError>()
+                .expect("HamError behind context");
+            assert!(matches!(ham, HamError::Connection { .. }));
+            assert!(matches!(
+                ham.conductor_error(),
+                Some(ConductorApiError::WebsocketError(WebsocketError::Close(_)))
+            ));
+            assert_eq!(ham.to_string(), "Failed to call zome");
+            assert!(is_connection_error(&e));
+        }
+
+        #[test]
+        fn guest_error_is_zome_guest_whatever_its_text() {
+            // A guest message that happens to contain a transport needle is
+            // still a guest error: the variant wins over the text.
+            let e = rendering(
+                ConductorApiError::ExternalApiWireError(ExternalApiWireError::RibosomeError(
+                    "Guest(\"peer connection reset while syncing\")".to_string(),
+                )),
+                "External API wire error: RibosomeError(\"Guest(\\\"peer connection reset while syncing\\\")\")",
+            );
+            assert!(matches!(
+                e.downcast_ref::<HamError>(),
+                Some(HamError::ZomeGuest { .. })
+            ));
+            assert!(!is_connection_error(&e), "got {e:#}");
+        }
+
+        #[test]
+        fn classify_names_the_rule_behind_a_typed_error() {
+            let e = rendering(
+                test_support::responder_dropped(),
+                "Websocket error: Other error: ResponderDropped",
+            );
+            assert_eq!(
+                classify(&e),
+                ErrorClass::new(Action::Reconnect, "responderdropped".into())
+            );
+            let e = rendering(
+                ConductorApiError::SignZomeCallError("no signature".to_string()),
+                "Unable to sign zome call: no signature",
+            );
+            assert_eq!(
+                classify(&e),
+                ErrorClass::new(Action::Fail, "HamError::Auth".into())
+            );
+        }
+
+        #[test]
+        fn guest_error_is_parsed_into_a_zome_error() {
+            let e: anyhow::Error = HamError::conductor(
+                "Failed to call zome",
+                test_support::guest_error("Amount must be positive (got \"-3\")"),
+            )
+            .for_call(CallTarget {
+                role_name: Some("ledger".to_string()),
+                cell_id: None,
+                zome_name: "transactions".to_string(),
+                fn_name: "create_spend".to_string(),
+            })
+            .into();
+            let zome = zome_error(&e).expect("guest error");
+            assert_eq!(zome.kind, ZomeErrorKind::Guest);
+            assert_eq!(zome.message, "Amount must be positive (got \"-3\")");
+            assert_eq!(
+                zome.to_string(),
+                "Guest error from ledger/transactions/create_spend: Amount must be positive (got \"-3\")"
+            );
+        }
+
+        #[test]
+        fn each_wasm_error_kind_is_recognized() {
+            let cases = [
+                (
+                    WasmErrorInner::Host("no such entry".to_string()),
+                    ZomeErrorKind::Host,
+                    "no such entry",
+                ),
+                (
+                    WasmErrorInner::Deserialize(vec![0x92, 0x01]),
+                    ZomeErrorKind::Deserialize,
+                    "[146, 1]",
+                ),
+                (
+                    WasmErrorInner::CallError("fn `nope` not found".to_string()),
+                    ZomeErrorKind::CallError,
+                    "fn `nope` not found",
+                ),
+                (
+                    WasmErrorInner::Serialize(SerializedBytesError::Serialize("bad".to_string())),
+                    ZomeErrorKind::Serialize,
+                    "Serialize(\"bad\")",
+                ),
+            ];
+            for (inner, kind, message) in cases {
+                let e: anyhow::Error =
+                    HamError::conductor("Failed to call zome", test_support::wasm_error(inner))
+                        .into();
+                let zome = zome_error(&e).expect("guest error");
+                assert_eq!((zome.kind, zome.message.as_str()), (kind, message), "{e:#}");
+                assert_eq!(zome.target, None);
+            }
+            // `ModuleDeserialize(` contains `Deserialize(` but isn't it.
+            let e: anyhow::Error = HamError::conductor(
+                "Failed to call zome",
+                test_support::wasm_error(WasmErrorInner::ModuleDeserialize("corrupt".to_string())),
+            )
+            .into();
+            assert_eq!(zome_error(&e).unwrap().kind, ZomeErrorKind::Other);
+        }
+
+        #[test]
+        fn unauthorized_call_is_an_auth_error() {
+            let e = rendering(
+                ConductorApiError::ExternalApiWireError(
+                    ExternalApiWireError::ZomeCallUnauthorized("no grant".to_string()),
+                ),
+                "External API wire error: ZomeCallUnauthorized(\"no grant\")",
+            );
+            assert!(matches!(
+                e.downcast_ref::<HamError>(),
+                Some(HamError::Auth { .. })
+            ));
+            assert!(is_permanent_error(&e));
+            assert_eq!(
+                classify(&e),
+                ErrorClass::new(Action::FailFast, "ZomeCallUnauthorized".into())
+            );
+            // Signing locally may just be lair being unavailable.
+            let e = rendering(
+                ConductorApiError::SignZomeCallError("no signature".to_string()),
+                "Unable to sign zome call: no signature",
+            );
+            assert!(!is_permanent_error(&e));
+        }
+
+        /// The conductor's close frame, as `holochain_websocket` reports it.
+        fn close_frame(frame: Option<CloseFrame>) -> ConductorApiError {
+            ConductorApiError::WebsocketError(WebsocketError::Close(format!("{frame:?}")))
+        }
+
+        #[test]
+        fn close_frame_after_token_is_a_rejected_token() {
+            let e: anyhow::Error = HamError::app_token_connect(
+                "ledger",
+                "Failed to connect to app interface",
+                close_frame(None),
+            )
+            .into();
+            assert!(matches!(
+                e.downcast_ref::<HamError>(),
+                Some(HamError::TokenRejected { .. })
+            ));
+            assert!(is_permanent_error(&e));
+            assert!(!is_connection_error(&e));
+            assert!(is_permanent_error(&flattened(&e)), "{e:#}");
+
+            // A refused connect means the conductor isn't up yet: retry.
+            let e: anyhow::Error = HamError::app_token_connect(
+                "ledger",
+                "Failed to connect to app interface",
+                ConductorApiError::WebsocketError(WebsocketError::Io(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "connection refused",
+                ))),
+            )
+            .into();
+            assert!(is_connection_error(&e), "{e:#}");
+            assert!(!is_permanent_error(&e));
+
+            let e: anyhow::Error = HamError::app_token_connect(
+                "ledger",
+                "Failed to connect to app interface",
+                close_frame(Some(CloseFrame {
+                    code: CloseCode::Policy,
+                    reason: "bad token".into(),
+                })),
+            )
+            .into();
+            assert!(matches!(
+                e.downcast_ref::<HamError>(),
+                Some(HamError::TokenRejected { .. })
+            ));
+        }
+
+        #[test]
+        fn dropped_link_during_token_connect_is_a_connection_failure() {
+            let cases = [
+                test_support::responder_dropped(),
+                test_support::connection_closed(),
+                ConductorApiError::WebsocketError(WebsocketError::Close(
+                    "ConnectionClosed".to_string(),
+                )),
+            ];
+            for source in cases {
+                let e: anyhow::Error = HamError::app_token_connect(
+                    "ledger",
+                    "Failed to connect to app interface",
+                    source,
+                )
+                .into();
+                assert!(matches!(
+                    e.downcast_ref::<HamError>(),
+                    Some(HamError::Connection { .. })
+                ));
+                assert!(!is_permanent_error(&e), "{e:#}");
+                assert_eq!(classify(&e).action, Action::Reconnect, "{e:#}");
+            }
         }
 
         #[tokio::test]
         async fn request_timeout_is_a_timeout_not_a_connection_failure() {
-            let elapsed =
-                tokio::time::timeout(std::time::Duration::ZERO, std::future::pending::<()>())
-                    .await
-                    .expect_err("a zero-length timeout must elapse");
-
             let e = rendering(
-                ConductorApiError::WebsocketError(WebsocketError::Timeout(elapsed)),
+                test_support::request_timeout().await,
                 "Websocket error: Timeout",
             );
             assert!(is_request_timeout(&e), "got {e:#}");
@@ -320,9 +1453,7 @@ mod tests {
         fn call_after_the_socket_closed_is_a_connection_failure() {
             // Every later call short-circuits here until `Ham` reconnects.
             let e = rendering(
-                ConductorApiError::WebsocketError(WebsocketError::Close(
-                    "No connection".to_string(),
-                )),
+                test_support::connection_closed(),
                 "Websocket error: Websocket closed: No connection",
             );
             assert!(is_connection_error(&e), "got {e:#}");
@@ -338,12 +1469,7 @@ mod tests {
             // failure raised on the send path arrives wrapped like this —
             // doubled prefix and all — rather than via the map drain above.
             let e = rendering(
-                ConductorApiError::WebsocketError(WebsocketError::Websocket(Box::new(
-                    tungstenite::Error::Io(std::io::Error::new(
-                        std::io::ErrorKind::BrokenPipe,
-                        "broken pipe",
-                    )),
-                ))),
+                test_support::broken_pipe(),
                 "Websocket error: Websocket error: IO error: broken pipe",
             );
             assert!(is_connection_error(&e), "got {e:#}");
@@ -457,9 +1583,7 @@ mod tests {
             // safe recovery is a rebuild, not a retry on a socket we can't
             // trust.
             let e = rendering(
-                ConductorApiError::WebsocketError(WebsocketError::Other(
-                    "ResponderDropped".to_string(),
-                )),
+                test_support::responder_dropped(),
                 "Websocket error: Other error: ResponderDropped",
             );
             assert!(is_connection_error(&e), "got {e:#}");
@@ -524,9 +1648,7 @@ mod tests {
             // the classifier keys on the message substring (variant-independent),
             // while the exact-string assertion pins the `Debug` derivation.
             let e = rendering(
-                ConductorApiError::ExternalApiWireError(ExternalApiWireError::RibosomeError(
-                    "Source chain error: deadline has elapsed".to_string(),
-                )),
+                test_support::source_chain_pressure(),
                 "External API wire error: RibosomeError(\"Source chain error: deadline has elapsed\")",
             );
             assert!(is_source_chain_pressure(&e), "got {e:#}");
@@ -8,21 +8,68 @@
 //!   client-side zome-call signing, and typed msgpack zome calls with an
 //!   explicit per-request timeout.
 //! * [`errors::is_connection_error`] &mdash; string-based classifier that
-//!   decides whether an [`anyhow::Error`] warrants rebuilding the socket.
+//!   decides whether an [`anyhow::Error`] warrants rebuilding the socket;
+//!   [`errors::classify`] runs every classifier in order and names the
+//!   recommended [`Action`]. The text rules behind them live in a
+//!   [`Classifier`] that consumers can extend at runtime or from config, with
+//!   a built-in table per [`HolochainLine`] (cargo features `hc-0_6`,
+//!   `hc-0_7`).
 //! * [`reconnect::connect_with_backoff`] and [`reconnect::compute_delay_ms`]
-//!   &mdash; shutdown-aware exponential-backoff reconnect loop with jitter.
+//!   &mdash; shutdown-aware exponential-backoff reconnect loop with jitter,
+//!   built on [`reconnect::retry_with_backoff`] for any async operation.
 //! * [`shutdown::install_shutdown_handler`] &mdash; returns a [`ShutdownRx`]
 //!   that flips to `true` on SIGINT/SIGTERM.
+//! * [`RetryPolicy`] with [`Ham::call_zome_with_retry`] &mdash; same-socket
+//!   retries with cooldowns for per-request timeouts and source-chain
+//!   pressure, surfacing connection errors for the reconnect layer.
+//! * [`Ham::subscribe_signals`] &mdash; typed, filterable app-signal streams,
+//!   one independent buffer per subscriber.
+//! * [`ManagedHam`] &mdash; the other three packaged: a [`Ham`] that rebuilds
+//!   its own socket on a classified connection error and retries the call.
+//! * `test_support` (feature `test-support`) &mdash; the real upstream errors
+//!   the classifiers see, for testing code that handles them.
 //!
-//! Daemons typically use all four. One-shot CLIs just construct [`Ham`] with
-//! a [`HamConfig::request_timeout_secs`] set and skip the rest.
+//! Daemons typically use [`ManagedHam`], or all four pieces by hand. One-shot
+//! CLIs just construct [`Ham`] with a [`HamConfig::request_timeout_secs`] set
+//! and skip the rest.
 
+#[cfg(not(any(feature = "hc-0_6", feature = "hc-0_7")))]
+compile_error!("enable at least one Holochain line feature: `hc-0_6` or `hc-0_7`");
+
+pub mod classifier;
 pub mod client;
+pub mod credentials;
 pub mod errors;
+pub mod managed;
 pub mod reconnect;
+pub mod retry;
 pub mod shutdown;
+pub mod signals;
+mod signing;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 
-pub use client::{Ham, HamConfig, LairSigning};
-pub use errors::{is_connection_error, is_request_timeout, is_source_chain_pressure};
-pub use reconnect::{compute_delay_ms, connect_with_backoff, BackoffConfig};
+pub use classifier::{
+    default_classifier, set_default_classifier, Classifier, HolochainLine, PatternConfig, Rule,
+    RuleClass, RuleConfig,
+};
+pub use client::{AppToken, Ham, HamConfig, LairSigning};
+pub use credentials::CredentialStore;
+pub use errors::{
+    classify, is_connection_error, is_permanent_error, is_request_timeout, is_schema_drift,
+    is_source_chain_pressure, zome_error, Action, CallTarget, ErrorClass, HamError, ZomeCall,
+    ZomeError, ZomeErrorKind,
+};
+pub use managed::{ManagedHam, Reconnecting};
+pub use reconnect::{
+    compute_delay_ms, connect_with_backoff, retry_with_backoff, Backoff, BackoffConfig,
+    BackoffError, JitterStrategy, ReconnectAttempt, ReconnectHooks, Schedule,
+};
+pub use retry::{RetryPolicy, RetryState};
 pub use shutdown::{install_shutdown_handler, ShutdownRx};
+pub use signals::{SignalError, SignalStream, TypedSignal};
+
+// The payload types in `Ham`'s clone-cell signatures, which `holochain_client`
+// doesn't re-export.
+pub use holochain_types::app::CreateCloneCellPayload;
+pub use holochain_types::prelude::CloneCellId;
@@ -0,0 +1,273 @@
+//! [`ManagedHam`] &mdash; a [`Ham`] that rebuilds its own socket.
+//!
+//! Packages the loop every daemon otherwise writes by hand: call, check
+//! [`is_connection_error`], [`connect_with_backoff`], swap the [`Ham`].
+
+use crate::client::{Ham, HamConfig};
+use crate::errors::{classify, is_connection_error};
+use crate::reconnect::{connect_with_backoff, BackoffConfig, BackoffError};
+use crate::retry::RetryPolicy;
+use crate::shutdown::ShutdownRx;
+use anyhow::Result;
+use holochain_client::ExternIO;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::warn;
+
+/// Returned by a [`ManagedHam`] call, inside the [`anyhow::Error`], when the
+/// call hit a connection error and was not retried. Recover it with
+/// `err.downcast_ref::<Reconnecting>()`.
+///
+/// `reconnected` tells the two cases apart: `true` means a fresh socket is
+/// already in place and the caller may retry (only returned for zome calls
+/// when [`ManagedHam::with_retry_after_reconnect`] is off); `false` means
+/// shutdown was signalled before a new connection came up.
+#[derive(Debug)]
+pub struct Reconnecting {
+    /// The connection error that triggered the rebuild.
+    pub cause: anyhow::Error,
+    /// Whether a fresh connection is in place.
+    pub reconnected: bool,
+}
+
+impl std::fmt::Display for Reconnecting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.reconnected {
+            write!(f, "Connection lost and rebuilt; call not retried")
+        } else {
+            write!(f, "Connection lost; shutdown requested before reconnecting")
+        }
+    }
+}
+
+impl std::error::Error for Reconnecting {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.cause.as_ref())
+    }
+}
+
+/// A [`Ham`] plus everything needed to rebuild it: on a classified
+/// connection error it reconnects via [`connect_with_backoff`] and retries
+/// the call on the fresh socket.
+///
+/// Concurrent callers that hit the same dead socket share one rebuild: the
+/// first runs the backoff loop, the rest wait for it and retry on its result.
+/// The [`BackoffConfig::hooks`] run for the first connection and every
+/// rebuild. Cheap to share behind an [`Arc`]; every method takes `&self`.
+pub struct ManagedHam {
+    cfg: HamConfig,
+    backoff: BackoffConfig,
+    shutdown: ShutdownRx,
+    retry_after_reconnect: bool,
+    current: RwLock<Current>,
+    // Held for the whole backoff loop, so at most one rebuild runs at a time.
+    rebuild: tokio::sync::Mutex<()>,
+}
+
+/// The live connection and how many rebuilds preceded it. A caller whose
+/// call failed on an older generation knows someone already replaced it.
+struct Current {
+    ham: Arc<Ham>,
+    generation: u64,
+}
+
+impl ManagedHam {
+    /// Establish the first connection via [`connect_with_backoff`]. Returns
+    /// `Ok(None)` if `shutdown` fires first, and the error if it
+    /// [`is_permanent_error`](crate::is_permanent_error) or the limits in
+    /// `backoff` run out (see [`BackoffError::into_anyhow`]).
+    pub async fn connect(
+        cfg: HamConfig,
+        backoff: BackoffConfig,
+        shutdown: ShutdownRx,
+    ) -> Result<Option<Self>> {
+        let mut rx = shutdown.clone();
+        let ham = match connect_with_backoff(|| Ham::connect(cfg.clone()), &backoff, &mut rx).await
+        {
+            Ok(ham) => ham,
+            Err(BackoffError::Shutdown) => return Ok(None),
+            Err(e) => return Err(e.into_anyhow()),
+        };
+        Ok(Some(Self {
+            cfg,
+            backoff,
+            shutdown,
+            retry_after_reconnect: true,
+            current: RwLock::new(Current {
+                ham: Arc::new(ham),
+                generation: 0,
+            }),
+            rebuild: tokio::sync::Mutex::new(()),
+        }))
+    }
+
+    /// Whether a zome call that failed on a dead socket is re-sent once the
+    /// socket is rebuilt (default `true`). Turn it off when zome calls aren't
+    /// idempotent: the failed call may still have committed, so the caller
+    /// gets a [`Reconnecting`] error and decides. [`ManagedHam::ping`] and
+    /// [`ManagedHam::app_info`] are read-only and always retried.
+    pub fn with_retry_after_reconnect(mut self, retry: bool) -> Self {
+        self.retry_after_reconnect = retry;
+        self
+    }
+
+    /// Call a zome function and decode the msgpack response into `R`,
+    /// reconnecting on a connection error (see [`Ham::call_zome`]).
+    pub async fn call_zome<I, R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+    ) -> Result<R>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        R: DeserializeOwned,
+    {
+        let payload = ExternIO::encode(payload)?;
+        self.run(self.retry_after_reconnect, |ham| {
+            let payload = payload.clone();
+            async move {
+                ham.call_zome_encoded(role_name, zome_name, fn_name, payload, None)
+                    .await
+            }
+        })
+        .await
+    }
+
+    /// [`ManagedHam::call_zome`] with its own deadline (see
+    /// [`Ham::call_zome_with_timeout`]).
+    pub async fn call_zome_with_timeout<I, R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+        timeout: Duration,
+    ) -> Result<R>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        R: DeserializeOwned,
+    {
+        let payload = ExternIO::encode(payload)?;
+        self.run(self.retry_after_reconnect, |ham| {
+            let payload = payload.clone();
+            async move {
+                ham.call_zome_encoded(role_name, zome_name, fn_name, payload, Some(timeout))
+                    .await
+            }
+        })
+        .await
+    }
+
+    /// [`ManagedHam::call_zome`] with same-socket retries per `policy` (see
+    /// [`Ham::call_zome_with_retry`]); the connection errors that policy
+    /// surfaces are then handled here by rebuilding the socket.
+    pub async fn call_zome_with_retry<I, R>(
+        &self,
+        role_name: &str,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+        policy: &RetryPolicy,
+    ) -> Result<R>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        R: DeserializeOwned,
+    {
+        let payload = ExternIO::encode(payload)?;
+        self.run(self.retry_after_reconnect, |ham| {
+            let payload = payload.clone();
+            async move {
+                ham.call_zome_encoded_with_retry(role_name, zome_name, fn_name, payload, policy)
+                    .await
+            }
+        })
+        .await
+    }
+
+    /// Round-trip probe (see [`Ham::ping`]). A dead socket is rebuilt and
+    /// probed again, so `Ok` means the connection is healthy *now*.
+    pub async fn ping(&self) -> Result<()> {
+        self.run(true, |ham| async move { ham.ping().await }).await
+    }
+
+    /// Fetch fresh app info from the conductor (see [`Ham::app_info`]).
+    pub async fn app_info(&self) -> Result<Option<holochain_client::AppInfo>> {
+        self.run(true, |ham| async move { ham.app_info().await })
+            .await
+    }
+
+    /// The current connection, for the parts of the [`Ham`] API this wrapper
+    /// doesn't proxy. Calls made on it directly are not reconnected.
+    pub fn ham(&self) -> Arc<Ham> {
+        self.current().0
+    }
+
+    fn current(&self) -> (Arc<Ham>, u64) {
+        let current = self.current.read().expect("ManagedHam lock poisoned");
+        (current.ham.clone(), current.generation)
+    }
+
+    /// Run `op` on the current connection; on a connection error, rebuild and
+    /// — if `retry` — run it once more on the fresh socket. A rebuild that
+    /// hits a permanent error, or runs out of `backoff` limits, returns that
+    /// error instead.
+    async fn run<T, F, Fut>(&self, retry: bool, op: F) -> Result<T>
+    where
+        F: Fn(Arc<Ham>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (ham, generation) = self.current();
+        match op(ham).await {
+            Err(e) if is_connection_error(&e) => match self.reconnect(generation, &e).await? {
+                Some(ham) if retry => op(ham).await,
+                reconnected => Err(Reconnecting {
+                    cause: e,
+                    reconnected: reconnected.is_some(),
+                }
+                .into()),
+            },
+            result => result,
+        }
+    }
+
+    /// Replace the connection that failed at `failed_generation`, unless a
+    /// concurrent caller already has. `Ok(None)` if shutdown fires first.
+    async fn reconnect(
+        &self,
+        failed_generation: u64,
+        cause: &anyhow::Error,
+    ) -> Result<Option<Arc<Ham>>> {
+        let _rebuilding = self.rebuild.lock().await;
+        let (ham, generation) = self.current();
+        if generation != failed_generation {
+            return Ok(Some(ham));
+        }
+        warn!(
+            event = "ham.disconnected",
+            rule = %classify(cause).rule,
+            error = %format_args!("{cause:#}"),
+            "rebuilding connection"
+        );
+        let mut shutdown = self.shutdown.clone();
+        let ham = match connect_with_backoff(
+            || Ham::connect(self.cfg.clone()),
+            &self.backoff,
+            &mut shutdown,
+        )
+        .await
+        {
+            Ok(ham) => ham,
+            Err(BackoffError::Shutdown) => return Ok(None),
+            Err(e) => return Err(e.into_anyhow()),
+        };
+        let ham = Arc::new(ham);
+        let mut current = self.current.write().expect("ManagedHam lock poisoned");
+        current.ham = ham.clone();
+        current.generation += 1;
+        Ok(Some(ham))
+    }
+}
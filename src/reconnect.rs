@@ -1,12 +1,20 @@
-//! Shutdown-aware exponential-backoff reconnect primitives.
+//! Shutdown-aware exponential-backoff retry and reconnect primitives.
 
 use crate::client::Ham;
+use crate::errors::{classify, is_permanent_error, Action};
 use crate::shutdown::ShutdownRx;
+use anyhow::Context;
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::future::Future;
-use std::time::Duration;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{error, info, warn};
 
-/// Configuration for [`connect_with_backoff`] and [`compute_delay_ms`].
+/// Configuration for [`retry_with_backoff`], [`connect_with_backoff`] and
+/// [`compute_delay_ms`].
 #[derive(Debug, Clone)]
 pub struct BackoffConfig {
     /// Initial delay between the first failed attempt and the retry.
@@ -14,9 +22,51 @@ pub struct BackoffConfig {
     /// Cap on the exponential growth.
     pub max_ms: u64,
     /// Number of consecutive failed attempts before escalating log level
-    /// from `warn!` to `error!`. The retry loop never gives up on its own;
-    /// callers exit by signalling shutdown.
+    /// from `warn!` to `error!`.
     pub escalate_after: u32,
+    /// Give up after this many attempts, the first included. `None` (the
+    /// default) retries until shutdown.
+    pub max_attempts: Option<u32>,
+    /// Give up rather than sleep past this long since the first attempt, for
+    /// jobs that must exit before their next run. An attempt already running
+    /// is not cut short. `None` (the default) retries until shutdown.
+    pub max_elapsed: Option<Duration>,
+    /// How each delay is randomized. Default
+    /// [`JitterStrategy::Proportional`]`(10)`.
+    pub jitter: JitterStrategy,
+    /// Seed for the jitter, for reproducible delays in tests. `None` (the
+    /// default) seeds each loop from the clock and process ID, so processes
+    /// restarted together still spread out.
+    pub seed: Option<u64>,
+    /// Per-class schedules for [`connect_with_backoff`], keyed by the
+    /// [`Action`] [`classify`] returns for the failed attempt: fast retries
+    /// for a conductor still booting (`Reconnect` — a refused connect is a
+    /// connection error), slow ones for `Cooldown`, `NoRetry` to stop on a
+    /// class. A class with no entry uses `initial_ms`/`max_ms`; so does
+    /// every error in [`retry_with_backoff`], which has no classifier.
+    /// Permanent errors are never retried whatever this says.
+    pub schedules: HashMap<Action, Schedule>,
+    /// Callbacks [`connect_with_backoff`] runs as it retries, and the
+    /// initializer it runs on each new connection. Not used by
+    /// [`retry_with_backoff`].
+    pub hooks: ReconnectHooks,
+}
+
+/// How [`connect_with_backoff`] retries one class of error; see
+/// [`BackoffConfig::schedules`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Exponential from `initial_ms`, capped at `max_ms`, jittered per
+    /// [`BackoffConfig::jitter`]. Counts its own attempts, from the first
+    /// error of the class.
+    Backoff {
+        /// Delay after the first error of the class.
+        initial_ms: u64,
+        /// Cap on the exponential growth.
+        max_ms: u64,
+    },
+    /// Return the error at once as [`BackoffError::NotRetryable`].
+    NoRetry,
 }
 
 impl Default for BackoffConfig {
@@ -25,84 +75,684 @@ impl Default for BackoffConfig {
             initial_ms: 1000,
             max_ms: 30_000,
             escalate_after: 5,
+            max_attempts: None,
+            max_elapsed: None,
+            jitter: JitterStrategy::default(),
+            seed: None,
+            schedules: HashMap::new(),
+            hooks: ReconnectHooks::default(),
         }
     }
 }
 
-/// Loop forever (until shutdown) trying to establish a fresh [`Ham`] via the
-/// provided async factory. Uses exponential backoff capped at `cfg.max_ms`
-/// with up to 10% jitter derived from the wall clock's sub-second nanos.
+impl BackoffConfig {
+    /// Builder: give up after `attempts` attempts.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Builder: give up once `elapsed` has passed since the first attempt.
+    pub fn with_max_elapsed(mut self, elapsed: Duration) -> Self {
+        self.max_elapsed = Some(elapsed);
+        self
+    }
+
+    /// Builder: randomize delays with `jitter`.
+    pub fn with_jitter(mut self, jitter: JitterStrategy) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Builder: draw jitter from a stream seeded with `seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Builder: retry errors that classify as `action` on `schedule`.
+    pub fn with_schedule(mut self, action: Action, schedule: Schedule) -> Self {
+        self.schedules.insert(action, schedule);
+        self
+    }
+
+    /// Builder: run `hooks` from [`connect_with_backoff`].
+    pub fn with_hooks(mut self, hooks: ReconnectHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+}
+
+/// A failed attempt, as a [`ReconnectHooks`] callback sees it.
+#[derive(Debug)]
+pub struct ReconnectAttempt<'a> {
+    /// The attempt that failed, from 0.
+    pub attempt: u32,
+    /// How long until the next attempt; `None` when there won't be one.
+    pub delay: Option<Duration>,
+    /// Why it failed.
+    pub error: &'a anyhow::Error,
+}
+
+type AttemptHook = Arc<dyn Fn(&ReconnectAttempt<'_>) + Send + Sync>;
+type ConnectedHook = Arc<
+    dyn for<'a> Fn(&'a Ham) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+        + Send
+        + Sync,
+>;
+
+/// Callbacks for [`connect_with_backoff`]'s lifecycle, alongside its tracing
+/// events: count failures, alert, or re-run initialization (warm caches,
+/// re-subscribe signals, re-register) on every new connection. Shared by
+/// every clone of the [`BackoffConfig`], and so by each rebuild of a
+/// [`ManagedHam`](crate::ManagedHam).
+#[derive(Clone, Default)]
+pub struct ReconnectHooks {
+    on_attempt: Option<AttemptHook>,
+    on_escalation: Option<AttemptHook>,
+    on_success: Option<Arc<dyn Fn(u32) + Send + Sync>>,
+    on_give_up: Option<AttemptHook>,
+    on_connected: Option<ConnectedHook>,
+}
+
+impl ReconnectHooks {
+    /// Call `f` on each failed attempt that will be retried.
+    pub fn on_attempt(mut self, f: impl Fn(&ReconnectAttempt<'_>) + Send + Sync + 'static) -> Self {
+        self.on_attempt = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` once, on the failed attempt that reaches
+    /// [`BackoffConfig::escalate_after`] (where logging moves to `error!`).
+    pub fn on_escalation(
+        mut self,
+        f: impl Fn(&ReconnectAttempt<'_>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_escalation = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` on success with the number of failed attempts before it.
+    pub fn on_success(mut self, f: impl Fn(u32) + Send + Sync + 'static) -> Self {
+        self.on_success = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` with the final error when the loop stops without a
+    /// connection: a permanent or [`Schedule::NoRetry`] error, or a limit
+    /// run out. Not on shutdown.
+    pub fn on_give_up(mut self, f: impl Fn(&ReconnectAttempt<'_>) + Send + Sync + 'static) -> Self {
+        self.on_give_up = Some(Arc::new(f));
+        self
+    }
+
+    /// Run `init` on each new connection before it is returned, e.g.
+    /// `|ham| Box::pin(async move { warm_caches(ham).await })`. An error
+    /// drops the connection and counts as a failed attempt.
+    pub fn on_connected<F>(mut self, init: F) -> Self
+    where
+        F: for<'a> Fn(&'a Ham) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.on_connected = Some(Arc::new(init));
+        self
+    }
+
+    fn observe(&self, stage: Stage<'_, anyhow::Error>) {
+        let (hook, attempt, delay, error) = match stage {
+            Stage::Retrying {
+                attempt,
+                delay,
+                error,
+            } => (&self.on_attempt, attempt, Some(delay), error),
+            Stage::Escalated {
+                attempt,
+                delay,
+                error,
+            } => (&self.on_escalation, attempt, Some(delay), error),
+            Stage::GaveUp { attempt, error } => (&self.on_give_up, attempt, None, error),
+            Stage::Succeeded { attempts } => {
+                if let Some(f) = &self.on_success {
+                    f(attempts);
+                }
+                return;
+            }
+        };
+        if let Some(f) = hook {
+            f(&ReconnectAttempt {
+                attempt,
+                delay,
+                error,
+            });
+        }
+    }
+}
+
+impl std::fmt::Debug for ReconnectHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectHooks")
+            .field("on_attempt", &self.on_attempt.is_some())
+            .field("on_escalation", &self.on_escalation.is_some())
+            .field("on_success", &self.on_success.is_some())
+            .field("on_give_up", &self.on_give_up.is_some())
+            .field("on_connected", &self.on_connected.is_some())
+            .finish()
+    }
+}
+
+/// Why [`retry_with_backoff`] (or [`connect_with_backoff`]) stopped without
+/// a value.
+#[derive(Debug)]
+pub enum BackoffError<E> {
+    /// `shutdown` flipped to `true`. Not a failure: exit cleanly.
+    Shutdown,
+    /// The error was not retryable; returned from the attempt that hit it.
+    NotRetryable(E),
+    /// [`BackoffConfig::max_attempts`] or [`BackoffConfig::max_elapsed`] ran
+    /// out.
+    GaveUp {
+        /// Attempts made, the first included.
+        attempts: u32,
+        /// Time since the first attempt.
+        elapsed: Duration,
+        /// The error from the final attempt.
+        last_error: E,
+    },
+}
+
+impl<E> BackoffError<E> {
+    /// Whether this is [`BackoffError::Shutdown`].
+    pub fn is_shutdown(&self) -> bool {
+        matches!(self, Self::Shutdown)
+    }
+
+    /// The error the last attempt failed with, unless shut down.
+    pub fn last_error(&self) -> Option<&E> {
+        match self {
+            Self::Shutdown => None,
+            Self::NotRetryable(e) | Self::GaveUp { last_error: e, .. } => Some(e),
+        }
+    }
+}
+
+impl BackoffError<anyhow::Error> {
+    /// As one `anyhow::Error`, keeping the last error's chain (and so its
+    /// [`classify`]) intact: a give-up becomes a context on it.
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            Self::Shutdown => anyhow::anyhow!("Shutdown requested"),
+            Self::NotRetryable(e) => e,
+            Self::GaveUp {
+                attempts,
+                elapsed,
+                last_error,
+            } => last_error.context(format!("Gave up after {attempts} attempts in {elapsed:?}")),
+        }
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for BackoffError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Shutdown => write!(f, "Shutdown requested"),
+            Self::NotRetryable(_) => write!(f, "Not retryable"),
+            Self::GaveUp {
+                attempts, elapsed, ..
+            } => write!(f, "Gave up after {attempts} attempts in {elapsed:?}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BackoffError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Shutdown => None,
+            Self::NotRetryable(e) => Some(e),
+            Self::GaveUp { last_error, .. } => Some(last_error),
+        }
+    }
+}
+
+/// Retry `op` until it succeeds, shutdown or a limit in `cfg`, sleeping
+/// between failures with exponential backoff capped at `cfg.max_ms` and
+/// jittered per `cfg.jitter` (see [`Backoff`]). For any async operation: lair
+/// reconnects, admin calls, HTTP requests.
+///
+/// `retryable` is asked once per failure, in order; an error it rejects is
+/// returned at once without sleeping. Each retried failure logs
+/// `ham.retry.attempt` at `warn!` up to `cfg.escalate_after`, then at
+/// `error!`, with the error's alternate (`{:#}`) `Display`.
+///
+/// Returns [`BackoffError::Shutdown`] if `shutdown` flips to `true` while we
+/// are sleeping or running `op`, letting the caller exit cleanly without
+/// further I/O, and [`BackoffError::GaveUp`] with the last error once
+/// [`BackoffConfig::max_attempts`] or [`BackoffConfig::max_elapsed`] runs out
+/// (logging `ham.retry.gave_up`).
+pub async fn retry_with_backoff<T, E, F, Fut, P>(
+    op: F,
+    retryable: P,
+    cfg: &BackoffConfig,
+    shutdown: &mut ShutdownRx,
+) -> Result<T, BackoffError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: Fn(&E) -> bool,
+    E: std::fmt::Display,
+{
+    backoff_loop(
+        op,
+        retryable,
+        |_| None,
+        |_| {},
+        cfg,
+        shutdown,
+        &RETRY_EVENTS,
+    )
+    .await
+}
+
+/// Loop (until shutdown) trying to establish a fresh [`Ham`] via the
+/// provided async factory: [`retry_with_backoff`] retrying every error but a
+/// permanent one.
 ///
-/// Each failed attempt logs at `warn!` up to `cfg.escalate_after`, then at
-/// `error!` so operator alerts can fire while the loop keeps retrying.
+/// Each failed attempt logs `ham.reconnect.attempt` at `warn!` up to
+/// `cfg.escalate_after`, then at `error!` so operator alerts can fire while
+/// the loop keeps retrying.
 ///
-/// Returns `None` if `shutdown` flips to `true` while we are sleeping or
-/// trying to connect, letting the caller exit cleanly without further I/O.
+/// Returns [`BackoffError::Shutdown`] if `shutdown` flips to `true` while
+/// we are sleeping or trying to connect, and [`BackoffError::GaveUp`] once a
+/// limit in `cfg` runs out (logging `ham.reconnect.gave_up`). Returns
+/// [`BackoffError::NotRetryable`] at once if the error
+/// [`is_permanent_error`]: a missing or disabled app or a rejected token
+/// won't fix itself, so spinning on it only hides the misconfiguration.
+/// [`BackoffError::into_anyhow`] turns any of them into an `anyhow::Error`.
+///
+/// Calls [`BackoffConfig::hooks`] as it goes; a connection whose
+/// [`ReconnectHooks::on_connected`] initializer fails is dropped and counts
+/// as a failed attempt, classified by the initializer's error.
 pub async fn connect_with_backoff<F, Fut>(
-    factory: F,
+    mut factory: F,
     cfg: &BackoffConfig,
     shutdown: &mut ShutdownRx,
-) -> Option<Ham>
+) -> Result<Ham, BackoffError<anyhow::Error>>
 where
-    F: Fn() -> Fut,
+    F: FnMut() -> Fut,
     Fut: Future<Output = anyhow::Result<Ham>>,
 {
+    let hooks = &cfg.hooks;
+    let connect = || {
+        let connecting = factory();
+        async move {
+            let ham = connecting.await?;
+            if let Some(init) = &hooks.on_connected {
+                init(&ham).await.context("Connection initializer failed")?;
+            }
+            Ok(ham)
+        }
+    };
+    // `retryable` runs once per failure, so counting its calls numbers the
+    // attempt that failed.
+    let attempt = Cell::new(0u32);
+    let retryable = |e: &anyhow::Error| {
+        let permanent = is_permanent_error(e);
+        if permanent {
+            error!(
+                event = "ham.reconnect.permanent",
+                attempt = attempt.get(),
+                rule = %classify(e).rule,
+                error = %format_args!("{e:#}"),
+                "permanent failure; not retrying"
+            );
+        }
+        attempt.set(attempt.get().saturating_add(1));
+        !permanent
+    };
+    let class = |e: &anyhow::Error| Some(classify(e).action);
+    let observe = |stage: Stage<'_, anyhow::Error>| hooks.observe(stage);
+    backoff_loop(
+        connect,
+        retryable,
+        class,
+        observe,
+        cfg,
+        shutdown,
+        &RECONNECT_EVENTS,
+    )
+    .await
+}
+
+/// The `event` names one caller of [`backoff_loop`] logs under.
+struct Events {
+    attempt: &'static str,
+    succeeded: &'static str,
+    gave_up: &'static str,
+    escalated: &'static str,
+}
+
+const RETRY_EVENTS: Events = Events {
+    attempt: "ham.retry.attempt",
+    succeeded: "ham.retry.succeeded",
+    gave_up: "ham.retry.gave_up",
+    escalated: "retry failing persistently; operator attention needed",
+};
+
+const RECONNECT_EVENTS: Events = Events {
+    attempt: "ham.reconnect.attempt",
+    succeeded: "ham.reconnected",
+    gave_up: "ham.reconnect.gave_up",
+    escalated: "reconnect failing persistently; operator attention needed",
+};
+
+/// A point in [`backoff_loop`] an observer hears about.
+enum Stage<'a, E> {
+    Retrying {
+        attempt: u32,
+        delay: Duration,
+        error: &'a E,
+    },
+    Escalated {
+        attempt: u32,
+        delay: Duration,
+        error: &'a E,
+    },
+    Succeeded {
+        attempts: u32,
+    },
+    GaveUp {
+        attempt: u32,
+        error: &'a E,
+    },
+}
+
+/// The loop behind both public entry points. `class` names the [`Action`]
+/// an error classifies as, picking its schedule from
+/// [`BackoffConfig::schedules`]; `None` uses the base schedule.
+/// `observe` sees every [`Stage`] as it happens.
+async fn backoff_loop<T, E, F, Fut, P, C, O>(
+    mut op: F,
+    retryable: P,
+    class: C,
+    observe: O,
+    cfg: &BackoffConfig,
+    shutdown: &mut ShutdownRx,
+    events: &Events,
+) -> Result<T, BackoffError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: Fn(&E) -> bool,
+    C: Fn(&E) -> Option<Action>,
+    O: Fn(Stage<'_, E>),
+    E: std::fmt::Display,
+{
+    let started = Instant::now();
+    let mut backoff = Backoff::new(cfg);
+    // One sequence per class, started on that class's first failure.
+    let mut by_class: HashMap<Action, Backoff> = HashMap::new();
     let mut attempt: u32 = 0;
     loop {
         if *shutdown.borrow() {
-            return None;
+            return Err(BackoffError::Shutdown);
         }
-        match factory().await {
-            Ok(ham) => {
+        match op().await {
+            Ok(value) => {
                 if attempt > 0 {
-                    info!(event = "ham.reconnected", attempts = attempt);
+                    info!(event = events.succeeded, attempts = attempt);
                 }
-                return Some(ham);
+                observe(Stage::Succeeded { attempts: attempt });
+                return Ok(value);
+            }
+            Err(e) if !retryable(&e) => {
+                observe(Stage::GaveUp { attempt, error: &e });
+                return Err(BackoffError::NotRetryable(e));
             }
             Err(e) => {
-                let delay_ms = compute_delay_ms(attempt, cfg);
+                let action = class(&e);
+                let delay_ms = match action.and_then(|a| Some((a, *cfg.schedules.get(&a)?))) {
+                    Some((_, Schedule::NoRetry)) => {
+                        observe(Stage::GaveUp { attempt, error: &e });
+                        return Err(BackoffError::NotRetryable(e));
+                    }
+                    Some((action, Schedule::Backoff { initial_ms, max_ms })) => by_class
+                        .entry(action)
+                        .or_insert_with(|| Backoff::with_bounds(cfg, initial_ms, max_ms, action))
+                        .next_delay_ms(),
+                    None => backoff.next_delay_ms(),
+                };
+                let attempts = attempt.saturating_add(1);
+                let elapsed = started.elapsed();
+                let out_of_attempts = cfg.max_attempts.is_some_and(|max| attempts >= max);
+                let out_of_time = cfg
+                    .max_elapsed
+                    .is_some_and(|max| elapsed + Duration::from_millis(delay_ms) > max);
+                if out_of_attempts || out_of_time {
+                    error!(
+                        event = events.gave_up,
+                        attempts,
+                        elapsed_ms = elapsed.as_millis() as u64,
+                        error = %format_args!("{e:#}"),
+                        "giving up"
+                    );
+                    observe(Stage::GaveUp { attempt, error: &e });
+                    return Err(BackoffError::GaveUp {
+                        attempts,
+                        elapsed,
+                        last_error: e,
+                    });
+                }
+                let delay = Duration::from_millis(delay_ms);
+                observe(Stage::Retrying {
+                    attempt,
+                    delay,
+                    error: &e,
+                });
+                if attempt == cfg.escalate_after {
+                    observe(Stage::Escalated {
+                        attempt,
+                        delay,
+                        error: &e,
+                    });
+                }
                 if attempt >= cfg.escalate_after {
                     error!(
-                        event = "ham.reconnect.attempt",
+                        event = events.attempt,
                         attempt,
                         delay_ms,
-                        error = %e,
-                        "reconnect failing persistently; operator attention needed"
+                        error = %format_args!("{e:#}"),
+                        "{}",
+                        events.escalated
                     );
                 } else {
                     warn!(
-                        event = "ham.reconnect.attempt",
+                        event = events.attempt,
                         attempt,
                         delay_ms,
-                        error = %e,
+                        error = %format_args!("{e:#}"),
                     );
                 }
-                attempt = attempt.saturating_add(1);
+                attempt = attempts;
 
                 tokio::select! {
-                    _ = tokio::time::sleep(Duration::from_millis(delay_ms)) => {}
-                    _ = shutdown.changed() => { return None; }
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.changed() => { return Err(BackoffError::Shutdown); }
                 }
             }
         }
     }
 }
 
-/// Compute the next reconnect delay: exponential (1,2,4,... *initial) capped
-/// at `cfg.max_ms`, plus up to 10% jitter seeded from the system clock's
-/// sub-second nanos (no extra crate dependency required). Pure function,
-/// unit-tested.
+/// How a backoff delay is randomized, so processes restarted together don't
+/// retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JitterStrategy {
+    /// The capped exponential delay exactly.
+    None,
+    /// The capped exponential delay plus up to this percentage of it. The
+    /// default is 10%.
+    Proportional(u32),
+    /// Anywhere from zero to the capped exponential delay ("full jitter").
+    /// Spreads a fleet out the most.
+    Full,
+    /// Anywhere from `initial_ms` to three times the previous delay, capped
+    /// at `max_ms` ("decorrelated jitter"). Grows like the exponential on
+    /// average without the attempt count fixing its range.
+    Decorrelated,
+}
+
+impl Default for JitterStrategy {
+    fn default() -> Self {
+        Self::Proportional(10)
+    }
+}
+
+/// The delays one retry loop sleeps, in order: [`BackoffConfig`]'s schedule
+/// and jitter, drawn from its own random stream. The loops in this module
+/// run one per call; build one from the same seeded config to reproduce
+/// their delays in a test.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial_ms: u64,
+    max_ms: u64,
+    jitter: JitterStrategy,
+    attempt: u32,
+    prev_ms: u64,
+    rng: SplitMix64,
+}
+
+impl Backoff {
+    /// A schedule at its first delay, seeded from [`BackoffConfig::seed`], or
+    /// from the clock, process ID and a counter if that's `None`.
+    pub fn new(cfg: &BackoffConfig) -> Self {
+        Self {
+            initial_ms: cfg.initial_ms,
+            max_ms: cfg.max_ms,
+            jitter: cfg.jitter,
+            attempt: 0,
+            prev_ms: cfg.initial_ms,
+            rng: SplitMix64(cfg.seed.unwrap_or_else(entropy)),
+        }
+    }
+
+    /// A [`Schedule::Backoff`] sequence for errors classified as `action`:
+    /// `cfg`'s jitter between other bounds, on a stream of its own.
+    fn with_bounds(cfg: &BackoffConfig, initial_ms: u64, max_ms: u64, action: Action) -> Self {
+        let salt = (action as u64 + 1).wrapping_mul(GOLDEN_GAMMA);
+        Self {
+            initial_ms,
+            max_ms,
+            prev_ms: initial_ms,
+            rng: SplitMix64(cfg.seed.map_or_else(entropy, |seed| seed ^ salt)),
+            ..Self::new(cfg)
+        }
+    }
+
+    /// How many delays have been drawn.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// The next delay, in milliseconds.
+    pub fn next_delay_ms(&mut self) -> u64 {
+        let capped = exponential_ms(self.attempt, self.initial_ms, self.max_ms);
+        let delay = jittered(
+            self.jitter,
+            capped,
+            self.prev_ms,
+            (self.initial_ms, self.max_ms),
+            &mut self.rng,
+        );
+        self.attempt = self.attempt.saturating_add(1);
+        self.prev_ms = delay;
+        delay
+    }
+}
+
+/// Compute the delay before retry `attempt` (from 0): exponential
+/// (1,2,4,... *initial) capped at `cfg.max_ms`, jittered per `cfg.jitter`.
+/// Stateless, so [`JitterStrategy::Decorrelated`] takes the un-jittered
+/// previous step as the previous delay; the retry loops draw from a
+/// [`Backoff`] instead. Deterministic when [`BackoffConfig::seed`] is set.
 pub fn compute_delay_ms(attempt: u32, cfg: &BackoffConfig) -> u64 {
+    let capped = exponential_ms(attempt, cfg.initial_ms, cfg.max_ms);
+    let prev = match attempt {
+        0 => cfg.initial_ms,
+        n => exponential_ms(n - 1, cfg.initial_ms, cfg.max_ms),
+    };
+    let seed = cfg.seed.unwrap_or_else(entropy);
+    let mut rng = SplitMix64(seed ^ u64::from(attempt).wrapping_mul(GOLDEN_GAMMA));
+    jittered(
+        cfg.jitter,
+        capped,
+        prev,
+        (cfg.initial_ms, cfg.max_ms),
+        &mut rng,
+    )
+}
+
+fn exponential_ms(attempt: u32, initial_ms: u64, max_ms: u64) -> u64 {
     let shift = attempt.min(20);
     let factor = 1u64.checked_shl(shift).unwrap_or(u64::MAX);
-    let base = cfg.initial_ms.saturating_mul(factor);
-    let capped = base.min(cfg.max_ms);
-    let jitter_range = (capped / 10).max(1);
+    initial_ms.saturating_mul(factor).min(max_ms)
+}
+
+fn jittered(
+    jitter: JitterStrategy,
+    capped: u64,
+    prev_ms: u64,
+    (initial_ms, max_ms): (u64, u64),
+    rng: &mut SplitMix64,
+) -> u64 {
+    match jitter {
+        JitterStrategy::None => capped,
+        JitterStrategy::Proportional(percent) => {
+            let range = (capped.saturating_mul(u64::from(percent)) / 100).max(1);
+            capped.saturating_add(rng.below(range))
+        }
+        JitterStrategy::Full => rng.below(capped.saturating_add(1)),
+        JitterStrategy::Decorrelated => {
+            let high = prev_ms.saturating_mul(3).max(initial_ms);
+            let span = high - initial_ms;
+            (initial_ms + rng.below(span.saturating_add(1))).min(max_ms)
+        }
+    }
+}
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A seed that differs across processes started in the same instant.
+fn entropy() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
     let nanos = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.subsec_nanos() as u64)
+        .map(|d| d.as_nanos() as u64)
         .unwrap_or(0);
-    capped.saturating_add(nanos % jitter_range)
+    let pid = u64::from(std::process::id());
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    SplitMix64(nanos ^ pid.rotate_left(32) ^ count.wrapping_mul(GOLDEN_GAMMA)).next()
+}
+
+/// SplitMix64: tiny, fast, and plenty for spreading retries; not for
+/// anything security-sensitive.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(GOLDEN_GAMMA);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound` (`bound > 0`), near enough: the modulo bias is
+    /// negligible for delays in milliseconds.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
 }
 
 #[cfg(test)]
@@ -114,6 +764,15 @@ mod tests {
             initial_ms: 1000,
             max_ms: 30_000,
             escalate_after: 5,
+            ..BackoffConfig::default()
+        }
+    }
+
+    fn fast() -> BackoffConfig {
+        BackoffConfig {
+            initial_ms: 1,
+            max_ms: 1,
+            ..cfg()
         }
     }
 
@@ -152,4 +811,301 @@ mod tests {
         let d = compute_delay_ms(u32::MAX, &c);
         assert!(d <= c.max_ms + (c.max_ms / 10).max(1));
     }
+
+    #[test]
+    fn no_jitter_is_the_exact_schedule() {
+        let c = cfg().with_jitter(JitterStrategy::None);
+        let mut b = Backoff::new(&c);
+        let delays: Vec<u64> = (0..7).map(|_| b.next_delay_ms()).collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 16_000, 30_000, 30_000]);
+        assert_eq!(compute_delay_ms(3, &c), 8000);
+    }
+
+    #[test]
+    fn seeded_delays_repeat_and_seeds_differ() {
+        for jitter in [
+            JitterStrategy::Proportional(10),
+            JitterStrategy::Full,
+            JitterStrategy::Decorrelated,
+        ] {
+            let draw = |seed| {
+                let mut b = Backoff::new(&cfg().with_jitter(jitter).with_seed(seed));
+                (0..8).map(|_| b.next_delay_ms()).collect::<Vec<_>>()
+            };
+            assert_eq!(draw(7), draw(7), "{jitter:?}");
+            assert_ne!(draw(7), draw(8), "{jitter:?}");
+            let c = cfg().with_jitter(jitter).with_seed(7);
+            assert_eq!(
+                compute_delay_ms(4, &c),
+                compute_delay_ms(4, &c),
+                "{jitter:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn full_jitter_stays_under_the_capped_delay() {
+        let mut b = Backoff::new(&cfg().with_jitter(JitterStrategy::Full).with_seed(1));
+        for attempt in 0..40 {
+            let cap = (1000u64 << attempt.min(20)).min(30_000);
+            assert!(b.next_delay_ms() <= cap, "attempt {attempt}");
+        }
+    }
+
+    #[test]
+    fn decorrelated_jitter_stays_between_initial_and_max() {
+        let c = cfg().with_jitter(JitterStrategy::Decorrelated).with_seed(3);
+        let mut b = Backoff::new(&c);
+        let delays: Vec<u64> = (0..40).map(|_| b.next_delay_ms()).collect();
+        assert!(
+            delays.iter().all(|d| (1000..=30_000).contains(d)),
+            "{delays:?}"
+        );
+        assert!(delays.iter().any(|&d| d > 3000), "{delays:?}");
+    }
+
+    #[tokio::test]
+    async fn each_class_backs_off_on_its_own_schedule() {
+        use std::sync::atomic::AtomicU32;
+
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let cfg = cfg()
+            .with_jitter(JitterStrategy::None)
+            .with_max_elapsed(Duration::from_millis(100))
+            .with_schedule(
+                Action::Reconnect,
+                Schedule::Backoff {
+                    initial_ms: 1,
+                    max_ms: 2,
+                },
+            )
+            .with_schedule(Action::Fail, Schedule::NoRetry);
+        let calls = AtomicU32::new(0);
+        let result = connect_with_backoff(
+            || async {
+                // Refused while the conductor boots, then a failure no
+                // schedule retries. The base schedule's 1s first delay would
+                // overrun `max_elapsed`.
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0..=2 => Err(anyhow::anyhow!(
+                        "IO error: Connection refused (os error 111)"
+                    )),
+                    _ => Err(anyhow::anyhow!("lair keystore is locked")),
+                }
+            },
+            &cfg,
+            &mut shutdown,
+        )
+        .await;
+        let Err(BackoffError::NotRetryable(err)) = result else {
+            panic!("stopped on the no-retry class");
+        };
+        assert_eq!(err.to_string(), "lair keystore is locked");
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn hooks_see_each_attempt_the_escalation_and_the_give_up() {
+        use std::sync::Mutex;
+
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = |stage: &'static str| {
+            let seen = seen.clone();
+            move |a: &ReconnectAttempt<'_>| {
+                seen.lock()
+                    .unwrap()
+                    .push((stage, a.attempt, a.delay.is_some()));
+            }
+        };
+        let hooks = ReconnectHooks::default()
+            .on_attempt(record("attempt"))
+            .on_escalation(record("escalation"))
+            .on_give_up(record("give_up"));
+        let cfg = BackoffConfig {
+            escalate_after: 1,
+            ..fast()
+        }
+        .with_max_attempts(3)
+        .with_hooks(hooks);
+        let result = connect_with_backoff(
+            || async { Err(anyhow::anyhow!("IO error: Connection refused")) },
+            &cfg,
+            &mut shutdown,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(BackoffError::GaveUp { attempts: 3, .. })
+        ));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                ("attempt", 0, true),
+                ("attempt", 1, true),
+                ("escalation", 1, true),
+                ("give_up", 2, false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn permanent_error_is_returned_without_retrying() {
+        use crate::errors::HamError;
+        use anyhow::Context;
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let calls = AtomicU32::new(0);
+        let result = connect_with_backoff(
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(HamError::NotInstalled {
+                    app_id: "ledger".to_string(),
+                })
+                .context("Failed to connect")
+            },
+            &cfg(),
+            &mut shutdown,
+        )
+        .await;
+        let Err(BackoffError::NotRetryable(err)) = result else {
+            panic!("permanent error returned as not retryable");
+        };
+        assert!(is_permanent_error(&err));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_retries_until_success() {
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let mut calls = 0u32;
+        let result = retry_with_backoff(
+            || {
+                calls += 1;
+                let n = calls;
+                async move {
+                    if n < 3 {
+                        Err(format!("refused #{n}"))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            },
+            |_: &String| true,
+            &fast(),
+            &mut shutdown,
+        )
+        .await;
+        assert_eq!(result.ok(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_returns_an_unretryable_error() {
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let mut calls = 0u32;
+        let result: Result<(), _> = retry_with_backoff(
+            || {
+                calls += 1;
+                async { Err("404 Not Found") }
+            },
+            |e: &&str| !e.starts_with("4"),
+            &cfg(),
+            &mut shutdown,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(BackoffError::NotRetryable("404 Not Found"))
+        ));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_stops_on_shutdown() {
+        let (tx, mut shutdown) = tokio::sync::watch::channel(false);
+        tx.send(true).unwrap();
+        let result: Result<(), BackoffError<String>> = retry_with_backoff(
+            || async { unreachable!("not called after shutdown") },
+            |_: &String| true,
+            &cfg(),
+            &mut shutdown,
+        )
+        .await;
+        assert!(result.unwrap_err().is_shutdown());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts_with_the_last_error() {
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let mut calls = 0u32;
+        let result: Result<(), _> = retry_with_backoff(
+            || {
+                calls += 1;
+                let n = calls;
+                async move { Err(format!("refused #{n}")) }
+            },
+            |_: &String| true,
+            &fast().with_max_attempts(3),
+            &mut shutdown,
+        )
+        .await;
+        let Err(BackoffError::GaveUp {
+            attempts,
+            last_error,
+            ..
+        }) = result
+        else {
+            panic!("gave up");
+        };
+        assert_eq!((attempts, last_error.as_str()), (3, "refused #3"));
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_rather_than_sleep_past_max_elapsed() {
+        let (_tx, mut shutdown) = tokio::sync::watch::channel(false);
+        let mut calls = 0u32;
+        let result: Result<(), BackoffError<String>> = retry_with_backoff(
+            || {
+                calls += 1;
+                async { Err("refused".to_string()) }
+            },
+            |_: &String| true,
+            &cfg().with_max_elapsed(Duration::from_millis(500)),
+            &mut shutdown,
+        )
+        .await;
+        // The first delay (1s) already overshoots, so there is no second try.
+        assert!(matches!(
+            result,
+            Err(BackoffError::GaveUp { attempts: 1, .. })
+        ));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn gave_up_keeps_the_last_error_classifiable() {
+        let e = BackoffError::GaveUp {
+            attempts: 4,
+            elapsed: Duration::from_secs(9),
+            last_error: anyhow::anyhow!("Websocket error: Websocket closed: No connection"),
+        }
+        .into_anyhow();
+        assert_eq!(e.to_string(), "Gave up after 4 attempts in 9s");
+        assert!(crate::is_connection_error(&e));
+    }
+
+    #[test]
+    fn source_is_the_wrapped_error() {
+        use std::error::Error as _;
+
+        let inner = std::io::Error::new(std::io::ErrorKind::NotFound, "App `ledger` not installed");
+        let e = BackoffError::NotRetryable(inner);
+        assert_eq!(e.to_string(), "Not retryable");
+        assert_eq!(
+            e.source().map(ToString::to_string).as_deref(),
+            Some("App `ledger` not installed")
+        );
+    }
 }
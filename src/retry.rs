@@ -0,0 +1,193 @@
+//! Same-socket retry policy for zome calls, driven by the
+//! [`errors`](crate::errors) classifiers.
+//!
+//! Each classifier implies a different reaction: a connection error needs a
+//! new socket (the caller's or [`ManagedHam`](crate::ManagedHam)'s job, so it
+//! is surfaced immediately), a per-request timeout a short cooldown on the
+//! existing socket, and source-chain pressure a growing back-off so a
+//! struggling conductor isn't hammered. Anything else is not retried.
+
+use crate::errors::{classify, Action};
+use std::time::Duration;
+
+/// Configuration for [`Ham::call_zome_with_retry`](crate::Ham::call_zome_with_retry).
+/// Retry budgets are per error class and count retries, not attempts: a
+/// policy with both at `0` makes a single attempt.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries allowed after [`is_request_timeout`](crate::is_request_timeout) errors.
+    pub timeout_retries: u32,
+    /// Cooldown before each timeout retry. Flat: the socket is healthy and
+    /// the call was merely slow.
+    pub timeout_cooldown_ms: u64,
+    /// Retries allowed after
+    /// [`is_source_chain_pressure`](crate::is_source_chain_pressure) errors.
+    pub pressure_retries: u32,
+    /// Cooldown before the first pressure retry; doubles on each further one.
+    pub pressure_cooldown_ms: u64,
+    /// Cap on the doubling pressure cooldown.
+    pub pressure_max_cooldown_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_retries: 2,
+            timeout_cooldown_ms: 1000,
+            pressure_retries: 3,
+            pressure_cooldown_ms: 2000,
+            pressure_max_cooldown_ms: 30_000,
+        }
+    }
+}
+
+/// Retries spent so far by one call, per error class.
+#[derive(Debug, Clone, Default)]
+pub struct RetryState {
+    /// Timeout retries already made.
+    pub timeouts: u32,
+    /// Pressure retries already made.
+    pub pressure: u32,
+}
+
+impl RetryPolicy {
+    /// Decide whether the call that failed with `err` should be retried on
+    /// the same socket, and after how long. Records the retry in `state`.
+    ///
+    /// Returns `None` — surface the error — unless [`classify`] says
+    /// [`Action::RetrySameSocket`] or [`Action::Cooldown`] (so never for a
+    /// connection error: a dead socket gets no same-socket retry), and once a
+    /// class's budget is spent. Pure function, unit-tested.
+    pub fn next_cooldown(&self, err: &anyhow::Error, state: &mut RetryState) -> Option<Duration> {
+        match classify(err).action {
+            Action::RetrySameSocket => {
+                if state.timeouts >= self.timeout_retries {
+                    return None;
+                }
+                state.timeouts += 1;
+                Some(Duration::from_millis(self.timeout_cooldown_ms))
+            }
+            Action::Cooldown => {
+                if state.pressure >= self.pressure_retries {
+                    return None;
+                }
+                let factor = 1u64.checked_shl(state.pressure.min(20)).unwrap_or(u64::MAX);
+                state.pressure += 1;
+                let delay = self
+                    .pressure_cooldown_ms
+                    .saturating_mul(factor)
+                    .min(self.pressure_max_cooldown_ms);
+                Some(Duration::from_millis(delay))
+            }
+            Action::Reconnect | Action::FailFast | Action::Fail => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            timeout_retries: 2,
+            timeout_cooldown_ms: 500,
+            pressure_retries: 3,
+            pressure_cooldown_ms: 1000,
+            pressure_max_cooldown_ms: 3000,
+        }
+    }
+
+    fn wrap(base: &'static str) -> anyhow::Error {
+        anyhow!(base).context("Failed to call zome")
+    }
+
+    #[test]
+    fn timeout_retries_with_flat_cooldown_until_budget_spent() {
+        let p = policy();
+        let mut s = RetryState::default();
+        let e = wrap("Websocket error: Timeout");
+        assert_eq!(
+            p.next_cooldown(&e, &mut s),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            p.next_cooldown(&e, &mut s),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(p.next_cooldown(&e, &mut s), None);
+    }
+
+    #[test]
+    fn pressure_cooldown_doubles_and_caps() {
+        let p = policy();
+        let mut s = RetryState::default();
+        let e = wrap("Source chain error: deadline has elapsed");
+        assert_eq!(
+            p.next_cooldown(&e, &mut s),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(
+            p.next_cooldown(&e, &mut s),
+            Some(Duration::from_millis(2000))
+        );
+        assert_eq!(
+            p.next_cooldown(&e, &mut s),
+            Some(Duration::from_millis(3000))
+        );
+        assert_eq!(p.next_cooldown(&e, &mut s), None);
+    }
+
+    #[test]
+    fn budgets_are_tracked_per_class() {
+        let p = policy();
+        let mut s = RetryState::default();
+        let timeout = wrap("Websocket error: Timeout");
+        let pressure = wrap("Source chain error: deadline has elapsed");
+        assert!(p.next_cooldown(&timeout, &mut s).is_some());
+        assert!(p.next_cooldown(&timeout, &mut s).is_some());
+        // Timeouts spent; pressure still has its own budget, starting from
+        // the initial cooldown.
+        assert_eq!(
+            p.next_cooldown(&pressure, &mut s),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(p.next_cooldown(&timeout, &mut s), None);
+    }
+
+    #[test]
+    fn connection_error_is_surfaced_immediately() {
+        let p = policy();
+        let mut s = RetryState::default();
+        let e = wrap("Websocket error: Websocket closed: No connection");
+        assert_eq!(p.next_cooldown(&e, &mut s), None);
+        assert_eq!((s.timeouts, s.pressure), (0, 0));
+    }
+
+    #[test]
+    fn unclassified_error_is_not_retried() {
+        let p = policy();
+        let mut s = RetryState::default();
+        let e = wrap("guest error: validation failed");
+        assert_eq!(p.next_cooldown(&e, &mut s), None);
+    }
+
+    #[test]
+    fn zero_budgets_make_a_single_attempt() {
+        let p = RetryPolicy {
+            timeout_retries: 0,
+            pressure_retries: 0,
+            ..policy()
+        };
+        let mut s = RetryState::default();
+        assert_eq!(
+            p.next_cooldown(&wrap("Websocket error: Timeout"), &mut s),
+            None
+        );
+        assert_eq!(
+            p.next_cooldown(&wrap("Source chain error: deadline has elapsed"), &mut s),
+            None
+        );
+    }
+}
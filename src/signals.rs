@@ -0,0 +1,230 @@
+//! Typed app-signal subscriptions &mdash; see [`Ham::subscribe_signals`].
+//!
+//! [`Ham::subscribe_signals`]: crate::Ham::subscribe_signals
+
+use holochain_client::{CellId, ExternIO};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::Stream;
+
+/// An app signal as received, before any subscriber decodes it. Shared
+/// between subscribers, so the payload is copied once per signal, not once
+/// per subscriber.
+#[derive(Debug)]
+pub(crate) struct RawSignal {
+    pub(crate) cell_id: CellId,
+    pub(crate) zome_name: String,
+    pub(crate) payload: ExternIO,
+}
+
+/// One app signal, decoded.
+#[derive(Debug, Clone)]
+pub struct TypedSignal<T> {
+    /// The cell that emitted the signal.
+    pub cell_id: CellId,
+    /// The zome that emitted the signal.
+    pub zome_name: String,
+    /// The msgpack payload, decoded.
+    pub payload: T,
+}
+
+/// A [`SignalStream`] item that isn't a signal. Neither ends the stream.
+#[derive(Debug)]
+pub enum SignalError {
+    /// This subscriber fell behind and the oldest `n` signals were dropped
+    /// for it (see [`HamConfig::signal_buffer`](crate::HamConfig::signal_buffer)).
+    /// Other subscribers are unaffected.
+    Lagged(u64),
+    /// A signal matched the filters but its payload didn't decode as `T`.
+    Decode {
+        /// The cell that emitted the signal.
+        cell_id: CellId,
+        /// The zome that emitted the signal.
+        zome_name: String,
+        /// Why decoding failed.
+        source: rmp_serde::decode::Error,
+    },
+}
+
+impl std::fmt::Display for SignalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lagged(n) => write!(f, "Signal subscriber lagged; {n} signals dropped"),
+            Self::Decode {
+                cell_id, zome_name, ..
+            } => write!(
+                f,
+                "Failed to decode signal from zome `{zome_name}` on {cell_id}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SignalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Lagged(_) => None,
+            Self::Decode { source, .. } => Some(source),
+        }
+    }
+}
+
+/// App signals from one [`Ham`](crate::Ham), decoded into `T`. Created by
+/// [`Ham::subscribe_signals`](crate::Ham::subscribe_signals); consume it with
+/// `tokio_stream::StreamExt`.
+///
+/// Each stream has its own buffer, so a slow subscriber only ever loses its
+/// own signals (reported as [`SignalError::Lagged`]). The stream ends when the
+/// `Ham` is dropped or closed; after a reconnect, subscribe on the new one.
+pub struct SignalStream<T> {
+    inner: BroadcastStream<Arc<RawSignal>>,
+    cell_id: Option<CellId>,
+    zome_name: Option<String>,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> SignalStream<T> {
+    pub(crate) fn new(rx: broadcast::Receiver<Arc<RawSignal>>) -> Self {
+        Self {
+            inner: BroadcastStream::new(rx),
+            cell_id: None,
+            zome_name: None,
+            _payload: PhantomData,
+        }
+    }
+
+    /// Only yield signals emitted by `cell_id`.
+    pub fn for_cell(mut self, cell_id: CellId) -> Self {
+        self.cell_id = Some(cell_id);
+        self
+    }
+
+    /// Only yield signals emitted by `zome_name`.
+    pub fn for_zome(mut self, zome_name: impl Into<String>) -> Self {
+        self.zome_name = Some(zome_name.into());
+        self
+    }
+
+    fn matches(&self, signal: &RawSignal) -> bool {
+        self.cell_id.as_ref().is_none_or(|c| *c == signal.cell_id)
+            && self
+                .zome_name
+                .as_ref()
+                .is_none_or(|z| *z == signal.zome_name)
+    }
+}
+
+impl<T: DeserializeOwned> Stream for SignalStream<T> {
+    type Item = Result<TypedSignal<T>, SignalError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let signal = match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(signal))) => signal,
+                Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(n)))) => {
+                    return Poll::Ready(Some(Err(SignalError::Lagged(n))))
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            if !self.matches(&signal) {
+                continue;
+            }
+            let item = match rmp_serde::from_slice(&signal.payload.0) {
+                Ok(payload) => Ok(TypedSignal {
+                    cell_id: signal.cell_id.clone(),
+                    zome_name: signal.zome_name.clone(),
+                    payload,
+                }),
+                Err(source) => Err(SignalError::Decode {
+                    cell_id: signal.cell_id.clone(),
+                    zome_name: signal.zome_name.clone(),
+                    source,
+                }),
+            };
+            return Poll::Ready(Some(item));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_client::AgentPubKey;
+    use holochain_types::prelude::DnaHash;
+    use tokio_stream::StreamExt;
+
+    fn cell(n: u8) -> CellId {
+        CellId::new(
+            DnaHash::from_raw_32(vec![n; 32]),
+            AgentPubKey::from_raw_32(vec![n; 32]),
+        )
+    }
+
+    fn raw(
+        cell_id: CellId,
+        zome_name: &str,
+        payload: impl serde::Serialize + std::fmt::Debug,
+    ) -> Arc<RawSignal> {
+        Arc::new(RawSignal {
+            cell_id,
+            zome_name: zome_name.to_string(),
+            payload: ExternIO::encode(payload).unwrap(),
+        })
+    }
+
+    #[tokio::test]
+    async fn subscribers_are_independent_and_filtered() {
+        let (tx, _) = broadcast::channel(16);
+        let all = SignalStream::<u32>::new(tx.subscribe());
+        let mut ledger = SignalStream::<u32>::new(tx.subscribe())
+            .for_cell(cell(1))
+            .for_zome("ledger");
+
+        tx.send(raw(cell(1), "pricing", 1u32)).unwrap();
+        tx.send(raw(cell(2), "ledger", 2u32)).unwrap();
+        tx.send(raw(cell(1), "ledger", 3u32)).unwrap();
+        drop(tx);
+
+        let all: Vec<u32> = all.map(|s| s.unwrap().payload).collect().await;
+        assert_eq!(all, [1, 2, 3]);
+        let signal = ledger.next().await.unwrap().unwrap();
+        assert_eq!(
+            (signal.cell_id, signal.zome_name.as_str(), signal.payload),
+            (cell(1), "ledger", 3)
+        );
+        assert!(ledger.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn lag_is_reported_and_the_stream_continues() {
+        let (tx, _) = broadcast::channel(2);
+        let mut stream = SignalStream::<u32>::new(tx.subscribe());
+        for n in 0..4u32 {
+            tx.send(raw(cell(1), "ledger", n)).unwrap();
+        }
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(SignalError::Lagged(2)))
+        ));
+        assert_eq!(stream.next().await.unwrap().unwrap().payload, 2);
+        assert_eq!(stream.next().await.unwrap().unwrap().payload, 3);
+    }
+
+    #[tokio::test]
+    async fn undecodable_payload_is_an_item_not_the_end() {
+        let (tx, _) = broadcast::channel(16);
+        let mut stream = SignalStream::<u32>::new(tx.subscribe());
+        tx.send(raw(cell(1), "ledger", "not a number")).unwrap();
+        tx.send(raw(cell(1), "ledger", 7u32)).unwrap();
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(&err, SignalError::Decode { zome_name, .. } if zome_name == "ledger"));
+        assert_eq!(stream.next().await.unwrap().unwrap().payload, 7);
+    }
+}
@@ -0,0 +1,117 @@
+//! Zome-call signers that accept new cells *after* the app websocket has
+//! connected, so [`Ham`](crate::Ham) can register clone cells it creates or
+//! enables mid-session.
+
+use async_trait::async_trait;
+use holochain_client::{AgentPubKey, AgentSigner, CellId, ClientAgentSigner, DynAgentSigner};
+use holochain_types::prelude::{CapSecret, Signature};
+use lair_keystore_api::LairClient;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The signer a [`Ham`](crate::Ham) registers cells with. Cheap to clone:
+/// every clone shares the credential map the websocket signs from.
+#[derive(Clone)]
+pub(crate) enum CellSigner {
+    /// Signs as the cell's own agent key via lair — no cap grant.
+    Lair(LairCellSigner),
+    /// Signs with a throwaway key authorized on chain (one cap grant per cell).
+    Client(ClientAgentSigner),
+}
+
+impl CellSigner {
+    /// Whether `cell_id` already has credentials — i.e. a zome call against it
+    /// would find a provenance to sign with.
+    pub(crate) fn has_credentials(&self, cell_id: &CellId) -> bool {
+        match self {
+            Self::Lair(s) => s.get_provenance(cell_id).is_some(),
+            Self::Client(s) => s.get_provenance(cell_id).is_some(),
+        }
+    }
+
+    /// The signing key and cap secret of the client-path grant `cell_id` is
+    /// signed with, if any. Always `None` on the lair path, which has no grant.
+    pub(crate) fn client_grant(&self, cell_id: &CellId) -> Option<(AgentPubKey, CapSecret)> {
+        match self {
+            Self::Lair(_) => None,
+            Self::Client(s) => Some((s.get_provenance(cell_id)?, s.get_cap_secret(cell_id)?)),
+        }
+    }
+
+    /// The `signing` field value of the `ham.connected` event.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Lair(_) => "lair",
+            Self::Client(_) => "client",
+        }
+    }
+}
+
+impl From<CellSigner> for DynAgentSigner {
+    fn from(signer: CellSigner) -> Self {
+        match signer {
+            CellSigner::Lair(s) => Arc::new(s),
+            CellSigner::Client(s) => s.into(),
+        }
+    }
+}
+
+/// Lair-backed signer equivalent to `holochain_client::LairAgentSigner`, but
+/// with a shared, `&self`-mutable credential map. The built-in signer's
+/// `add_credentials` takes `&mut self`, so once it is wrapped in `Arc<dyn>`
+/// and handed to the websocket no further cell can be registered — which
+/// rules out clone cells created after connect.
+#[derive(Clone)]
+pub(crate) struct LairCellSigner {
+    lair_client: Arc<LairClient>,
+    credentials: Arc<RwLock<HashMap<CellId, AgentPubKey>>>,
+}
+
+impl LairCellSigner {
+    pub(crate) fn new(lair_client: LairClient) -> Self {
+        Self {
+            lair_client: Arc::new(lair_client),
+            credentials: Arc::default(),
+        }
+    }
+
+    /// Sign zome calls to `cell_id` as the cell's own agent key.
+    pub(crate) fn add_credentials(&self, cell_id: CellId) {
+        let provenance = cell_id.agent_pubkey().clone();
+        self.credentials
+            .write()
+            .expect("lair credential lock poisoned")
+            .insert(cell_id, provenance);
+    }
+}
+
+#[async_trait]
+impl AgentSigner for LairCellSigner {
+    async fn sign(
+        &self,
+        _cell_id: &CellId,
+        provenance: AgentPubKey,
+        data_to_sign: Arc<[u8]>,
+    ) -> anyhow::Result<Signature> {
+        let public_key: [u8; 32] = provenance.get_raw_32().try_into()?;
+        let signature = self
+            .lair_client
+            .sign_by_pub_key(public_key.into(), None, data_to_sign)
+            .await?;
+        Ok(Signature(*signature.0))
+    }
+
+    fn get_provenance(&self, cell_id: &CellId) -> Option<AgentPubKey> {
+        self.credentials
+            .read()
+            .expect("lair credential lock poisoned")
+            .get(cell_id)
+            .cloned()
+    }
+
+    /// Lair signs as the agent itself (the implicit `ChainAuthor` grant), so
+    /// there is no cap secret to present.
+    fn get_cap_secret(&self, _cell_id: &CellId) -> Option<CapSecret> {
+        None
+    }
+}
@@ -0,0 +1,143 @@
+//! Real upstream error values, for testing code that handles `Ham` errors
+//! (cargo feature `test-support`).
+//!
+//! `holochain_client` re-exports neither `WebsocketError` nor
+//! `ExternalApiWireError`, so a consumer can't build the errors
+//! [`is_connection_error`](crate::is_connection_error) and friends see in
+//! production. These are the values ham's own pinning tests build, from the
+//! upstream crates ham resolves, so a consumer's tests move with ham's
+//! `holochain_client` version rather than with a hand-copied string.
+//!
+//! Each builder returns the raw [`ConductorApiError`]; [`zome_call_failure`]
+//! wraps one the way [`Ham::call_zome`](crate::Ham::call_zome) returns it.
+
+use crate::errors::{CallTarget, HamError, ZomeCall};
+use holochain_client::ConductorApiError;
+use holochain_conductor_api::ExternalApiWireError;
+use holochain_wasmer_common::WasmError;
+pub use holochain_wasmer_common::WasmErrorInner;
+use holochain_websocket::WebsocketError;
+use std::time::Duration;
+
+/// A call on a socket that is already closed:
+/// `"Websocket error: Websocket closed: No connection"`.
+/// [`Action::Reconnect`](crate::Action::Reconnect).
+pub fn connection_closed() -> ConductorApiError {
+    ConductorApiError::WebsocketError(WebsocketError::Close("No connection".to_string()))
+}
+
+/// An io failure on the send path once the socket is up:
+/// `"Websocket error: Websocket error: IO error: broken pipe"`.
+/// [`Action::Reconnect`](crate::Action::Reconnect).
+pub fn broken_pipe() -> ConductorApiError {
+    ConductorApiError::WebsocketError(WebsocketError::Websocket(Box::new(tungstenite::Error::Io(
+        std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken pipe"),
+    ))))
+}
+
+/// An in-flight request whose responder was dropped as the socket went down:
+/// `"Websocket error: Other error: ResponderDropped"`.
+/// [`Action::Reconnect`](crate::Action::Reconnect).
+pub fn responder_dropped() -> ConductorApiError {
+    ConductorApiError::WebsocketError(WebsocketError::Other("ResponderDropped".to_string()))
+}
+
+/// A request that outlived its timeout:
+/// `"Websocket error: Timeout"`.
+/// [`Action::RetrySameSocket`](crate::Action::RetrySameSocket).
+///
+/// Async because tokio's `Elapsed` can only come from a timeout that really
+/// elapsed; needs a runtime with the time driver, as `#[tokio::test]` has.
+pub async fn request_timeout() -> ConductorApiError {
+    let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+        .await
+        .expect_err("a zero-length timeout always elapses");
+    ConductorApiError::WebsocketError(WebsocketError::Timeout(elapsed))
+}
+
+/// A conductor refusing a call under source-chain backpressure:
+/// `"External API wire error: RibosomeError(\"Source chain error: deadline has elapsed\")"`.
+/// [`Action::Cooldown`](crate::Action::Cooldown).
+pub fn source_chain_pressure() -> ConductorApiError {
+    ConductorApiError::ExternalApiWireError(ExternalApiWireError::RibosomeError(
+        "Source chain error: deadline has elapsed".to_string(),
+    ))
+}
+
+/// A zome returning `Err(wasm_error!(message))`, as the ribosome reports it.
+/// Parses to a [`ZomeError`](crate::ZomeError) of kind
+/// [`Guest`](crate::ZomeErrorKind::Guest) with `message` as its message.
+pub fn guest_error(message: impl Into<String>) -> ConductorApiError {
+    wasm_error(WasmErrorInner::Guest(message.into()))
+}
+
+/// A zome call failing with any `WasmError`, as the ribosome reports it. The
+/// `WasmError` rendering is upstream's own `Display`; the prefix around it is
+/// copied from the conductor, which isn't a dependency.
+pub fn wasm_error(inner: WasmErrorInner) -> ConductorApiError {
+    let wasm = WasmError {
+        module_path: "ledger_integrity::validation".to_string(),
+        line: 42,
+        error: inner,
+    };
+    ConductorApiError::ExternalApiWireError(ExternalApiWireError::RibosomeError(format!(
+        "Wasm runtime error while working with Ribosome: RuntimeError: {wasm}"
+    )))
+}
+
+/// `error` in the shape [`Ham::call_zome`](crate::Ham::call_zome) returns it
+/// from a call to `zome_name`/`fn_name` on `role_name`: a [`HamError`] under
+/// a [`ZomeCall`] context, request ID 0.
+pub fn zome_call_failure(
+    role_name: &str,
+    zome_name: &str,
+    fn_name: &str,
+    error: ConductorApiError,
+) -> anyhow::Error {
+    let target = CallTarget {
+        role_name: Some(role_name.to_string()),
+        cell_id: None,
+        zome_name: zome_name.to_string(),
+        fn_name: fn_name.to_string(),
+    };
+    anyhow::Error::from(HamError::conductor("Failed to call zome", error).for_call(target.clone()))
+        .context(ZomeCall {
+            request_id: 0,
+            target,
+            elapsed: Duration::ZERO,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{classify, zome_error, Action, ZomeErrorKind};
+
+    #[test]
+    fn zome_call_failure_matches_what_ham_returns() {
+        let e = zome_call_failure(
+            "ledger",
+            "transactions",
+            "create_spend",
+            guest_error("Amount must be positive"),
+        );
+        assert_eq!(
+            e.to_string(),
+            "Zome call #0 to ledger/transactions/create_spend failed after 0ns"
+        );
+        assert!(e.downcast_ref::<HamError>().is_some());
+        let zome = zome_error(&e).expect("guest error");
+        assert_eq!(zome.kind, ZomeErrorKind::Guest);
+        assert_eq!(
+            zome.target.as_ref().map(|t| t.fn_name.as_str()),
+            Some("create_spend")
+        );
+        let e = zome_call_failure(
+            "ledger",
+            "transactions",
+            "create_spend",
+            connection_closed(),
+        );
+        assert_eq!(classify(&e).action, Action::Reconnect);
+    }
+}